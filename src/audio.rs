@@ -0,0 +1,109 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Instant,
+};
+
+use eyre::{Context, Result, eyre};
+
+/// The quietest level shown on a meter, in dB relative to full scale
+const MIN_DB: f32 = -48.0;
+/// Levels are measured over this many of the most recently played seconds of audio
+const LEVEL_WINDOW_SECONDS: f64 = 1.0 / 40.0;
+
+/// Plays back a WAV file as a series of audio levels, looping at the end of the file
+#[derive(Debug)]
+pub struct WavLevels {
+    /// Mono samples scaled to -1 to 1
+    samples: Vec<f32>,
+    sample_rate: u32,
+    /// The playback position in samples
+    position: f64,
+    /// When the level was last read, or `None` before playback starts
+    last_read: Option<Instant>,
+}
+
+impl WavLevels {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut reader = hound::WavReader::open(path)
+            .wrap_err_with(|| format!("Unable to open audio file: {:?}", path))?;
+
+        let spec = reader.spec();
+
+        let interleaved = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .samples::<f32>()
+                .collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Int => {
+                let full_scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 / full_scale))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        // Mix the channels down to mono
+        let samples = interleaved
+            .chunks(spec.channels as usize)
+            .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+            .collect::<Vec<_>>();
+
+        if samples.is_empty() {
+            return Err(eyre!("Audio file is empty: {:?}", path));
+        }
+
+        Ok(Self {
+            samples,
+            sample_rate: spec.sample_rate,
+            position: 0.0,
+            last_read: None,
+        })
+    }
+
+    /// Opens and decodes the file on a background thread, so that large files don't hold up the
+    /// LEDs. The result is received once it has loaded.
+    pub fn open_in_background(path: PathBuf) -> Receiver<Result<Self>> {
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            // The receiver is gone if the file was replaced before it loaded
+            let _ = sender.send(Self::open(&path));
+        });
+
+        receiver
+    }
+
+    /// Advances the playback in real time (regardless of the program speed) and returns the level
+    /// (0 to 1) of the audio played most recently
+    pub fn next_level(&mut self) -> f32 {
+        let now = Instant::now();
+        let sample_count = self.samples.len();
+
+        if let Some(last_read) = self.last_read {
+            let played = now.duration_since(last_read).as_secs_f64() * self.sample_rate as f64;
+            self.position = (self.position + played) % sample_count as f64;
+        }
+
+        self.last_read = Some(now);
+
+        let window = ((self.sample_rate as f64 * LEVEL_WINDOW_SECONDS) as usize).max(1);
+        let position = self.position as usize;
+
+        // The samples leading up to the playback position, looping back from the end of the file
+        let sum_of_squares = (0..window)
+            .map(|offset| {
+                self.samples[(position + sample_count - offset % sample_count) % sample_count]
+            })
+            .map(|sample| sample * sample)
+            .sum::<f32>();
+
+        let rms = (sum_of_squares / window as f32).sqrt();
+
+        // Meters are scaled in dB so that quiet passages are still visible
+        let db = 20.0 * rms.max(f32::MIN_POSITIVE).log10();
+        ((db - MIN_DB) / -MIN_DB).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use eyre::{Context, Result};
+
+use crate::preset::Preset;
+
+/// Everything restored at startup after a crash or reboot
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SavedState {
+    pub paused: bool,
+    /// The program, its parameters and the global settings
+    pub preset: Preset,
+}
+
+impl SavedState {
+    pub fn from_model(model: &crate::Model) -> Result<Self> {
+        Ok(Self {
+            paused: model.paused,
+            preset: Preset::from_model(model)?,
+        })
+    }
+
+    pub fn load(file_path: impl AsRef<Path>) -> Result<Self> {
+        let file_path = file_path.as_ref();
+
+        let state: Self = toml::from_str(&fs::read_to_string(file_path)
+            .wrap_err_with(|| format!("Unable to read state file: {:?}", file_path))?
+        )
+            .wrap_err_with(|| format!("Invalid state file: {:?}", file_path))?;
+
+        state.preset.validate()
+            .wrap_err_with(|| format!("Invalid state file: {:?}", file_path))?;
+
+        Ok(state)
+    }
+
+    /// Restores the program and settings (without fading)
+    pub fn restore(self, model: &mut crate::Model) -> Result<()> {
+        model.paused = self.paused;
+        self.preset.recall(model, 0.0)
+    }
+}
+
+/// Periodically writes the current state to a file so that it can be restored at startup
+#[derive(Debug)]
+pub struct Autosave {
+    file_path: PathBuf,
+    interval: Duration,
+    last_save: Instant,
+    /// The contents of the state file as of the last save
+    saved: Option<String>,
+}
+
+impl Autosave {
+    pub fn new(file_path: PathBuf, interval_seconds: f32) -> Self {
+        Self {
+            file_path,
+            interval: Duration::from_secs_f32(interval_seconds),
+            last_save: Instant::now(),
+            saved: None,
+        }
+    }
+
+    /// Restores the state saved by a previous run, if there is one
+    pub fn restore(&self, model: &mut crate::Model) -> Result<()> {
+        if !self.file_path.exists() {
+            return Ok(());
+        }
+
+        SavedState::load(&self.file_path)?.restore(model)?;
+
+        println!("Restored the state saved in {:?}", self.file_path);
+        Ok(())
+    }
+
+    /// Saves the state if it changed and the interval has passed since the last save
+    pub fn update(&mut self, model: &crate::Model) {
+        if self.last_save.elapsed() < self.interval {
+            return;
+        }
+
+        self.last_save = Instant::now();
+
+        if let Err(err) = self.save(model) {
+            warn!("{:?}", err);
+        }
+    }
+
+    fn save(&mut self, model: &crate::Model) -> Result<()> {
+        let state = toml::to_string(&SavedState::from_model(model)?)?;
+
+        if self.saved.as_ref() == Some(&state) {
+            return Ok(());
+        }
+
+        // Write to a temporary file first so that a crash mid-write can't corrupt the state file
+        let tmp_path = self.file_path.with_extension("tmp");
+
+        fs::write(&tmp_path, &state)
+            .and_then(|_| fs::rename(&tmp_path, &self.file_path))
+            .wrap_err_with(|| format!("Unable to write state file: {:?}", self.file_path))?;
+
+        trace!("State saved to {:?}", self.file_path);
+
+        self.saved = Some(state);
+        Ok(())
+    }
+}
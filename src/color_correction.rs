@@ -0,0 +1,283 @@
+use eyre::{Result, eyre};
+use nannou::color::Rgb;
+use serde::Deserialize;
+
+use crate::dither::TemporalDither;
+
+/// Corrections applied to each LED when it is encoded for an output
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct ColorCorrection {
+    /// The order the LED strip expects the color channels in
+    pub order: ColorOrder,
+    /// Each channel is raised to this power. 1.0 leaves the colors unchanged.
+    pub gamma: f32,
+    /// Red, green and blue multipliers (0 to 1) used to balance the strip's white point
+    pub white_balance: [f32; 3],
+    /// Adds a white channel (RGBW) derived from the color using this algorithm. RGB only if
+    /// `None`.
+    pub white: Option<WhiteExtraction>,
+    /// Temporally dithers the 8 bit output for smoother fades at low brightness
+    pub dither: bool,
+    #[serde(skip)]
+    dither_state: TemporalDither,
+}
+
+/// How the white channel of RGBW LEDs is derived from the color
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WhiteExtraction {
+    /// The white common to all three channels is moved to the white channel. Most accurate
+    /// colors.
+    Subtract,
+    /// The white common to all three channels is added to the white channel without removing it
+    /// from the others. Brightest.
+    Add,
+    /// The white channel is always off
+    Off,
+}
+
+/// An encoded LED: 3 channels or 4 for RGBW
+#[derive(Debug, Clone, Copy)]
+pub struct Pixel {
+    channels: [u8; 4],
+    len: usize,
+}
+
+impl Pixel {
+    pub fn as_slice(&self) -> &[u8] {
+        &self.channels[..self.len]
+    }
+
+    /// The white channel of RGBW pixels
+    pub fn white(&self) -> Option<u8> {
+        if self.len == 4 {
+            Some(self.channels[3])
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self {
+            order: ColorOrder::Rgb,
+            gamma: 1.0,
+            white_balance: [1.0, 1.0, 1.0],
+            white: None,
+            dither: false,
+            dither_state: TemporalDither::default(),
+        }
+    }
+}
+
+impl ColorOrder {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "RGB" => Ok(Self::Rgb),
+            "RBG" => Ok(Self::Rbg),
+            "GRB" => Ok(Self::Grb),
+            "GBR" => Ok(Self::Gbr),
+            "BRG" => Ok(Self::Brg),
+            "BGR" => Ok(Self::Bgr),
+            _ => Err(eyre!("Invalid color order: {:?}", name)),
+        }
+    }
+
+    /// Reorders red, green and blue values into the strip's channel order
+    pub fn apply<T>(&self, [r, g, b]: [T; 3]) -> [T; 3] {
+        match self {
+            Self::Rgb => [r, g, b],
+            Self::Rbg => [r, b, g],
+            Self::Grb => [g, r, b],
+            Self::Gbr => [g, b, r],
+            Self::Brg => [b, r, g],
+            Self::Bgr => [b, g, r],
+        }
+    }
+}
+
+impl WhiteExtraction {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "subtract" => Ok(Self::Subtract),
+            "add" => Ok(Self::Add),
+            "off" => Ok(Self::Off),
+            _ => Err(eyre!("Invalid white extraction: {:?}", name)),
+        }
+    }
+
+    /// Splits red, green and blue values into new red, green and blue values and a white value
+    pub fn apply(&self, [r, g, b]: [f32; 3]) -> ([f32; 3], f32) {
+        let white = r.min(g).min(b);
+
+        match self {
+            Self::Subtract => ([r - white, g - white, b - white], white),
+            Self::Add => ([r, g, b], white),
+            Self::Off => ([r, g, b], 0.0),
+        }
+    }
+}
+
+impl ColorCorrection {
+    pub fn validate(&self) -> Result<()> {
+        if !self.gamma.is_finite() || self.gamma <= 0.0 {
+            return Err(eyre!("gamma must be greater than 0, got: {}", self.gamma));
+        }
+
+        if self.white_balance.iter().any(|value| !(0.0..=1.0).contains(value)) {
+            return Err(eyre!(
+                "white_balance values must be between 0 and 1, got: {:?}",
+                self.white_balance,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Number of channels per encoded LED (3 for RGB, 4 for RGBW)
+    pub fn channels_per_pixel(&self) -> usize {
+        if self.white.is_some() { 4 } else { 3 }
+    }
+
+    /// Converts every LED to corrected 8 bit values in the strip's channel order. The white
+    /// channel of RGBW pixels always comes last.
+    pub fn encode_frame(&mut self, led_strips: &crate::LedStripVec) -> Vec<Vec<Pixel>> {
+        let led_count = led_strips.iter().map(|led_strip| led_strip.len()).sum::<usize>();
+
+        if self.dither {
+            self.dither_state.resize(led_count * 4);
+        }
+
+        let mut led_index = 0;
+
+        led_strips
+            .iter()
+            .map(|led_strip| {
+                led_strip
+                    .iter()
+                    .map(|led| {
+                        let pixel = self.encode(led_index, led);
+                        led_index += 1;
+                        pixel
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The corrected levels (0 to 1) of the red, green, blue and white channels a LED is sent
+    /// with, before they are reordered and quantized. The white channel is 0 for RGB outputs.
+    pub fn channel_levels(&self, color: &crate::LedColor) -> [f32; 4] {
+        let rgb = Into::<Rgb>::into(*color);
+
+        let channels = [
+            rgb.red.clamp(0.0, 1.0),
+            rgb.green.clamp(0.0, 1.0),
+            rgb.blue.clamp(0.0, 1.0),
+        ];
+
+        let (channels, white) = match self.white {
+            Some(white_extraction) => white_extraction.apply(channels),
+            None => (channels, 0.0),
+        };
+
+        let mut levels = [0.0f32; 4];
+
+        for (channel_index, value) in channels.iter().enumerate() {
+            levels[channel_index] = value.powf(self.gamma) * self.white_balance[channel_index];
+        }
+
+        levels[3] = white.powf(self.gamma);
+        levels
+    }
+
+    fn encode(&mut self, led_index: usize, color: &crate::LedColor) -> Pixel {
+        let [red, green, blue, white] = self.channel_levels(color);
+        let [c1, c2, c3] = self.order.apply([red, green, blue]);
+
+        let values = [c1, c2, c3, white];
+        let len = self.channels_per_pixel();
+
+        let mut pixel = Pixel {
+            channels: [0; 4],
+            len,
+        };
+
+        for (channel_index, value) in values[..len].iter().enumerate() {
+            pixel.channels[channel_index] = if self.dither {
+                self.dither_state.quantize(led_index * 4 + channel_index, *value)
+            } else {
+                (value * 255.0).round() as u8
+            };
+        }
+
+        pixel
+    }
+
+    pub fn receive_osc_packet<'a>(
+        &mut self,
+        addr: &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+    ) -> Result<()> {
+        use nannou_osc::Type::*;
+
+        match (addr, args) {
+            (["order"], [
+                String(order),
+            ]) => {
+                self.order = ColorOrder::from_name(order)?;
+            }
+            (["gamma"], [
+                Float(gamma),
+            ]) => {
+                if !gamma.is_finite() || *gamma <= 0.0 {
+                    return Err(eyre!("Invalid gamma: {:?}", gamma));
+                }
+                self.gamma = *gamma;
+            }
+            (["white"], [
+                String(white_extraction),
+            ]) => {
+                self.white = match white_extraction.as_str() {
+                    "none" => None,
+                    name => Some(WhiteExtraction::from_name(name)?),
+                };
+            }
+            (["dither"], [
+                Float(enabled),
+            ]) => {
+                self.dither = *enabled != 0.0;
+            }
+            // Input is between 0 and 255 for each channel
+            (["whitebalance"], [
+                Float(red),
+                Float(green),
+                Float(blue),
+            ]) => {
+                self.white_balance = [
+                    (red / 255.0).clamp(0.0, 1.0),
+                    (green / 255.0).clamp(0.0, 1.0),
+                    (blue / 255.0).clamp(0.0, 1.0),
+                ];
+            }
+            _ => {
+                return Err(eyre!("Unsupported packet received. addr: {:?} args: {:?}", addr, args))
+            }
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,506 @@
+use std::{
+    fs,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::{Path, PathBuf},
+};
+
+use nannou::color::hsl;
+use serde::{Deserialize, Serialize};
+use eyre::{Context, Result, eyre};
+
+use crate::{
+    color_correction::ColorCorrection,
+    feedback::FeedbackConfig,
+    midi::MidiConfig,
+    power::PowerConfig,
+    tempo::TempoConfig,
+    outputs::{ARTNET_PORT, DMX_UNIVERSE_SIZE, LedRange, MAX_ARTNET_UNIVERSE, OscRoute, SACN_PORT, SacnUniverses},
+    topology::Topology,
+    transition::TransitionConfig,
+};
+
+/// Startup settings, loaded from the TOML file passed via `--config`. Every field is optional.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct Config {
+    /// UDP port to listen for OSC control messages on
+    pub osc_port: u16,
+    /// TCP port to serve the OSCQuery description of the OSC addresses on (disabled if unset)
+    pub oscquery_port: Option<u16>,
+    /// Where the current settings are sent back to over OSC
+    pub feedback: FeedbackConfig,
+    /// MIDI input mapped to the OSC addresses and the tempo
+    pub midi: MidiConfig,
+    /// Local UDP port that frames are sent to the LED controller(s) from
+    pub bind_port: u16,
+    /// Host and port of each LED controller. Falls back to the `LED_CONTROLLER` environment
+    /// variable when empty. Every controller is sent all of the LEDs unless `routes` are set.
+    pub controllers: Vec<String>,
+    /// Sends each strip (or range of LEDs) to its own controller and OSC address
+    pub routes: Vec<RouteConfig>,
+    /// Color correction for the LEDs sent to the controllers over OSC
+    pub osc_correction: ColorCorrection,
+    /// Art-Net (ArtDmx) outputs
+    pub artnet: Vec<ArtNetConfig>,
+    /// E1.31 (sACN) outputs
+    pub sacn: Vec<SacnConfig>,
+    /// Records every frame to this file
+    pub record: Option<PathBuf>,
+    /// Scales frames down to keep the power supplies within their ratings
+    pub power: Option<PowerConfig>,
+    /// Name of the program to run at startup (eg. "blink")
+    pub program: String,
+    /// WAV file played back (on a loop) as the audio level of the vumeter program
+    pub audio_file: Option<PathBuf>,
+    /// Playlist of programs played by the preprogram program
+    pub playlist: Option<PathBuf>,
+    /// The program and settings are saved to this file periodically and restored from it at
+    /// startup
+    pub state_file: Option<PathBuf>,
+    /// Seconds between saves of the state file
+    pub autosave_interval: f32,
+    /// Directory that presets are saved to
+    pub presets_dir: PathBuf,
+    /// How the LEDs change over from one program to the next
+    pub transition: TransitionConfig,
+    /// Program speed. 40 advances the program by one frame per update.
+    pub fps: f32,
+    /// The musical clock and the programs that run in time with it
+    pub tempo: TempoConfig,
+    /// Updates per second when running with `--headless`. The visualizer updates once per
+    /// rendered frame instead.
+    pub update_rate: f32,
+    pub global_brightness: f32,
+    pub brightness1: f32,
+    pub brightness2: f32,
+    pub color1: ColorConfig,
+    pub color2: ColorConfig,
+    pub topology: Topology,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ColorConfig {
+    /// Hue in degrees (0 to 360)
+    pub hue: f32,
+    /// Saturation (0 to 1)
+    pub saturation: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RouteConfig {
+    /// Host and port of the LED controller
+    pub controller: String,
+    /// Index of the LED strip in the topology
+    pub strip: usize,
+    /// OSC address to send the LEDs to. Defaults to "/led_strips/<strip>".
+    pub address: Option<String>,
+    /// Index of the first LED to send. Defaults to the start of the strip.
+    pub start: Option<usize>,
+    /// Number of LEDs to send. Defaults to the rest of the strip.
+    pub len: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ArtNetConfig {
+    /// IP address (optionally with a port) of the Art-Net node. May be a broadcast address.
+    pub target: String,
+    /// The universe of the first 170 LEDs (128 if RGBW). Following LEDs continue on consecutive
+    /// universes.
+    #[serde(default)]
+    pub start_universe: u16,
+    #[serde(default)]
+    pub correction: ColorCorrection,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SacnConfig {
+    /// IP address (optionally with a port) of the receiver. Universes are multicast when omitted.
+    pub target: Option<String>,
+    /// The universe of the first 170 LEDs (128 if RGBW). Following LEDs continue on consecutive
+    /// universes.
+    #[serde(default = "default_sacn_universe")]
+    pub start_universe: u16,
+    /// The starting universe of each LED strip. Overrides `start_universe`.
+    pub strip_universes: Option<Vec<u16>>,
+    /// Receivers use the data from the highest priority source (0 to 200)
+    #[serde(default = "default_sacn_priority")]
+    pub priority: u8,
+    #[serde(default = "default_sacn_source_name")]
+    pub source_name: String,
+    #[serde(default)]
+    pub correction: ColorCorrection,
+}
+
+fn default_sacn_universe() -> u16 {
+    1
+}
+
+fn default_sacn_priority() -> u8 {
+    100
+}
+
+fn default_sacn_source_name() -> String {
+    "nannou-led-controller".to_string()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            osc_port: 8000,
+            oscquery_port: None,
+            feedback: FeedbackConfig::default(),
+            midi: MidiConfig::default(),
+            bind_port: 49781,
+            controllers: vec![],
+            routes: vec![],
+            osc_correction: ColorCorrection::default(),
+            artnet: vec![],
+            sacn: vec![],
+            record: None,
+            power: None,
+            program: "blink".to_string(),
+            audio_file: None,
+            playlist: None,
+            state_file: None,
+            autosave_interval: 5.0,
+            presets_dir: PathBuf::from("presets"),
+            transition: TransitionConfig::default(),
+            fps: 40.0,
+            tempo: TempoConfig::default(),
+            update_rate: 60.0,
+            global_brightness: 1.0,
+            brightness1: 0.5,
+            brightness2: 0.5,
+            color1: ColorConfig { hue: 0.0, saturation: 1.0 },
+            color2: ColorConfig { hue: 0.0, saturation: 0.0 },
+            topology: Topology::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(file_path: impl AsRef<Path>) -> Result<Self> {
+        let file_path = file_path.as_ref();
+
+        let config: Self = toml::from_str(&fs::read_to_string(file_path)
+            .wrap_err_with(|| format!("Unable to read config: {:?}", file_path))?
+        )
+            .wrap_err_with(|| format!("Invalid config: {:?}", file_path))?;
+
+        config.validate()
+            .wrap_err_with(|| format!("Invalid config: {:?}", file_path))?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.topology.validate()?;
+
+        if !self.fps.is_finite() || self.fps < 0.0 {
+            return Err(eyre!("fps must be a positive number, got: {}", self.fps));
+        }
+
+        if !self.update_rate.is_finite() || self.update_rate < 1.0 {
+            return Err(eyre!("update_rate must be at least 1, got: {}", self.update_rate));
+        }
+
+        if !self.autosave_interval.is_finite() || self.autosave_interval <= 0.0 {
+            return Err(eyre!(
+                "autosave_interval must be greater than 0, got: {}",
+                self.autosave_interval,
+            ));
+        }
+
+        for (route_index, route) in self.routes.iter().enumerate() {
+            route.led_range(&self.topology)
+                .wrap_err_with(|| format!("Invalid route #{}", route_index))?;
+
+            if let Some(address) = &route.address {
+                if !address.starts_with('/') {
+                    return Err(eyre!(
+                        "Invalid route #{}: OSC address must start with a '/', got: {:?}",
+                        route_index,
+                        address,
+                    ));
+                }
+            }
+        }
+
+        self.osc_correction.validate()
+            .wrap_err("Invalid osc_correction")?;
+
+        for (artnet_index, artnet) in self.artnet.iter().enumerate() {
+            artnet.validate(&self.topology)
+                .wrap_err_with(|| format!("Invalid artnet output #{}", artnet_index))?;
+        }
+
+        for (sacn_index, sacn) in self.sacn.iter().enumerate() {
+            sacn.validate(&self.topology)
+                .wrap_err_with(|| format!("Invalid sacn output #{}", sacn_index))?;
+        }
+
+        if let Some(power) = &self.power {
+            power.validate(self.topology.strips.len())
+                .wrap_err("Invalid power config")?;
+        }
+
+        self.transition.validate()
+            .wrap_err("Invalid transition config")?;
+
+        self.feedback.validate()
+            .wrap_err("Invalid feedback config")?;
+
+        self.tempo.validate()
+            .wrap_err("Invalid tempo config")?;
+
+        self.midi.validate()
+            .wrap_err("Invalid midi config")?;
+
+        let fractions = [
+            ("global_brightness", self.global_brightness),
+            ("brightness1", self.brightness1),
+            ("brightness2", self.brightness2),
+            ("color1.saturation", self.color1.saturation),
+            ("color2.saturation", self.color2.saturation),
+        ];
+
+        for (field, value) in fractions.iter() {
+            if !(0.0..=1.0).contains(value) {
+                return Err(eyre!("{} must be between 0 and 1, got: {}", field, value));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the OSC routes from the config. Without any `routes` every LED is sent to each
+    /// controller as "/led_strips/0". OSC output is optional if other outputs are configured.
+    pub fn osc_routes(&self) -> Result<Vec<OscRoute>> {
+        if !self.routes.is_empty() {
+            return self.routes
+                .iter()
+                .map(|route| {
+                    Ok(OscRoute {
+                        controller: resolve_controller(&route.controller)?,
+                        address: route.address
+                            .clone()
+                            .unwrap_or_else(|| format!("/led_strips/{}", route.strip)),
+                        leds: Some(route.led_range(&self.topology)?),
+                    })
+                })
+                .collect();
+        }
+
+        let controllers = if self.controllers.is_empty() {
+            let led_controller = match std::env::var("LED_CONTROLLER") {
+                Ok(led_controller) => led_controller,
+                Err(_) if self.has_non_osc_outputs() => return Ok(vec![]),
+                Err(err) => {
+                    return Err(err)
+                        .wrap_err("No LED controllers configured and LED_CONTROLLER is not set")
+                }
+            };
+
+            vec![led_controller]
+        } else {
+            self.controllers.clone()
+        };
+
+        controllers
+            .iter()
+            .map(|controller| {
+                Ok(OscRoute {
+                    controller: resolve_controller(controller)?,
+                    address: "/led_strips/0".to_string(),
+                    leds: None,
+                })
+            })
+            .collect()
+    }
+
+    fn has_non_osc_outputs(&self) -> bool {
+        !self.artnet.is_empty() || !self.sacn.is_empty() || self.record.is_some()
+    }
+
+    pub fn color1(&self) -> crate::LedColor {
+        hsl(
+            self.color1.hue / 360.0,
+            self.color1.saturation,
+            self.brightness1 * self.global_brightness,
+        )
+    }
+
+    pub fn color2(&self) -> crate::LedColor {
+        hsl(
+            self.color2.hue / 360.0,
+            self.color2.saturation,
+            self.brightness2 * self.global_brightness,
+        )
+    }
+}
+
+impl RouteConfig {
+    pub fn led_range(&self, topology: &Topology) -> Result<LedRange> {
+        let strip = topology.strips
+            .get(self.strip)
+            .ok_or_else(|| eyre!("LED strip #{} does not exist", self.strip))?;
+
+        let start = self.start.unwrap_or(0);
+        let end = start + self.len.unwrap_or_else(|| strip.len.saturating_sub(start));
+
+        if start >= end || end > strip.len {
+            return Err(eyre!(
+                "LEDs {}..{} are out of range for LED strip #{} ({} LEDs)",
+                start,
+                end,
+                self.strip,
+                strip.len,
+            ));
+        }
+
+        Ok(LedRange {
+            strip: self.strip,
+            start,
+            end,
+        })
+    }
+}
+
+impl ArtNetConfig {
+    pub fn validate(&self, topology: &Topology) -> Result<()> {
+        self.target_addr()?;
+        self.correction.validate()?;
+
+        let pixels_per_universe = DMX_UNIVERSE_SIZE / self.correction.channels_per_pixel();
+        let led_count: usize = topology.strips.iter().map(|strip| strip.len).sum();
+        let universe_count = led_count.div_ceil(pixels_per_universe).max(1);
+
+        let end_universe = self.start_universe as usize + universe_count - 1;
+
+        if end_universe > MAX_ARTNET_UNIVERSE as usize {
+            return Err(eyre!(
+                "Universes must be between 0 and {}, got: {} to {}",
+                MAX_ARTNET_UNIVERSE,
+                self.start_universe,
+                end_universe,
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn target_addr(&self) -> Result<SocketAddr> {
+        resolve_target(&self.target, ARTNET_PORT)
+    }
+}
+
+impl SacnConfig {
+    pub fn validate(&self, topology: &Topology) -> Result<()> {
+        self.target_addr()?;
+        self.correction.validate()?;
+
+        if self.priority > 200 {
+            return Err(eyre!("priority must be between 0 and 200, got: {}", self.priority));
+        }
+
+        let pixels_per_universe = DMX_UNIVERSE_SIZE / self.correction.channels_per_pixel();
+        let universes_needed = |led_count: usize| led_count.div_ceil(pixels_per_universe);
+
+        // Check that the first and last universe of each range are valid
+        let universe_ranges = match &self.strip_universes {
+            Some(strip_universes) => {
+                if strip_universes.len() != topology.strips.len() {
+                    return Err(eyre!(
+                        "strip_universes must list a universe for each of the {} LED strips",
+                        topology.strips.len(),
+                    ));
+                }
+
+                strip_universes
+                    .iter()
+                    .zip(topology.strips.iter())
+                    .map(|(start_universe, strip)| {
+                        (*start_universe as usize, universes_needed(strip.len))
+                    })
+                    .collect()
+            }
+            None => {
+                let led_count = topology.strips.iter().map(|strip| strip.len).sum();
+                vec![(self.start_universe as usize, universes_needed(led_count))]
+            }
+        };
+
+        let mut universe_ranges = universe_ranges
+            .into_iter()
+            .map(|(start_universe, universe_count)| {
+                (start_universe, start_universe + universe_count.max(1) - 1)
+            })
+            .collect::<Vec<_>>();
+
+        for (start_universe, end_universe) in universe_ranges.iter() {
+            if *start_universe < 1 || *end_universe > 63999 {
+                return Err(eyre!(
+                    "Universes must be between 1 and 63999, got: {} to {}",
+                    start_universe,
+                    end_universe,
+                ));
+            }
+        }
+
+        // Strips can't share a universe
+        universe_ranges.sort_unstable();
+
+        for ranges in universe_ranges.windows(2) {
+            if let [(first_start, first_end), (second_start, second_end)] = ranges {
+                if second_start <= first_end {
+                    return Err(eyre!(
+                        "strip_universes overlap: universes {} to {} and {} to {}",
+                        first_start,
+                        first_end,
+                        second_start,
+                        second_end,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn target_addr(&self) -> Result<Option<SocketAddr>> {
+        self.target
+            .as_ref()
+            .map(|target| resolve_target(target, SACN_PORT))
+            .transpose()
+    }
+
+    pub fn universes(&self) -> SacnUniverses {
+        match &self.strip_universes {
+            Some(strip_universes) => SacnUniverses::PerStrip(strip_universes.clone()),
+            None => SacnUniverses::Consecutive(self.start_universe),
+        }
+    }
+}
+
+/// Resolves an IP address (using the default port) or a host and port
+fn resolve_target(target: &str, default_port: u16) -> Result<SocketAddr> {
+    match target.parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, default_port)),
+        Err(_) => resolve_controller(target),
+    }
+}
+
+fn resolve_controller(controller: &str) -> Result<SocketAddr> {
+    controller
+        .to_socket_addrs()
+        .wrap_err_with(|| format!(
+            "LED controller should be a valid host and port: {:?}",
+            controller,
+        ))?
+        .next()
+        .ok_or_else(|| eyre!("LED controller address not found: {:?}", controller))
+}
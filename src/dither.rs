@@ -0,0 +1,26 @@
+/// Temporal dithering. Each channel's rounding error is carried over to the same channel in the
+/// next frame so that, averaged over several frames, the LEDs show the levels between 8 bit steps.
+#[derive(Debug, Clone, Default)]
+pub struct TemporalDither {
+    errors: Vec<f32>,
+}
+
+impl TemporalDither {
+    /// Sets the number of channels per frame, resetting the carried errors if it changed
+    pub fn resize(&mut self, channel_count: usize) {
+        if self.errors.len() != channel_count {
+            self.errors = vec![0.0; channel_count];
+        }
+    }
+
+    /// Quantizes a channel value (0 to 1) to 8 bits
+    pub fn quantize(&mut self, channel_index: usize, value: f32) -> u8 {
+        let target = value * 255.0 + self.errors[channel_index];
+        let quantized = target.round().clamp(0.0, 255.0);
+
+        // Don't carry error from values that can never be reached (eg. above full brightness)
+        self.errors[channel_index] = (target - quantized).clamp(-0.5, 0.5);
+
+        quantized as u8
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use eyre::{Context, Result, eyre};
+
+/// OSC messages (address and arguments) that set every setting to its current value
+pub type OscState = Vec<(String, Vec<nannou_osc::Type>)>;
+
+/// The most senders that are sent feedback at once. The sender heard from least recently is
+/// dropped to make room for a new one.
+const MAX_SENDERS: usize = 32;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct FeedbackConfig {
+    /// Sends feedback to every host that OSC messages are received from
+    pub reply_to_senders: bool,
+    /// Port to send feedback to senders on. Defaults to the port each message was sent from.
+    pub reply_port: Option<u16>,
+    /// Seconds without a message from a sender before it is no longer sent feedback
+    pub sender_timeout: f32,
+    /// Hosts and ports that are always sent feedback (eg. "192.168.1.60:9000")
+    pub targets: Vec<String>,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            reply_to_senders: true,
+            reply_port: None,
+            sender_timeout: 600.0,
+            targets: vec![],
+        }
+    }
+}
+
+impl FeedbackConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.sender_timeout.is_finite() || self.sender_timeout <= 0.0 {
+            return Err(eyre!("sender_timeout must be greater than 0, got: {}", self.sender_timeout));
+        }
+
+        self.target_addrs().map(|_| ())
+    }
+
+    pub fn target_addrs(&self) -> Result<Vec<SocketAddr>> {
+        self.targets
+            .iter()
+            .map(|target| {
+                target
+                    .to_socket_addrs()
+                    .wrap_err_with(|| format!("Feedback target should be a valid host and port: {:?}", target))?
+                    .next()
+                    .ok_or_else(|| eyre!("Feedback target address not found: {:?}", target))
+            })
+            .collect()
+    }
+}
+
+/// Sends the settings back to the control surfaces whenever they change so that their faders and
+/// buttons stay in sync, even when the settings are changed from another controller
+#[derive(Debug)]
+pub struct Feedback {
+    socket: UdpSocket,
+    reply_to_senders: bool,
+    reply_port: Option<u16>,
+    sender_timeout: Duration,
+    /// The configured targets, which are always sent feedback
+    targets: Vec<SocketAddr>,
+    /// Where feedback for each sender is sent, along with when the sender was last heard from
+    senders: Vec<(SocketAddr, Instant)>,
+    /// Targets that are sent every setting on the next update
+    pending_dumps: Vec<SocketAddr>,
+    /// The settings as they were last sent
+    last_state: OscState,
+}
+
+impl Feedback {
+    pub fn new(config: &FeedbackConfig) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .wrap_err("Unable to open a UDP port for OSC feedback")?;
+
+        let targets = config.target_addrs()?;
+
+        Ok(Self {
+            socket,
+            reply_to_senders: config.reply_to_senders,
+            reply_port: config.reply_port,
+            sender_timeout: Duration::from_secs_f32(config.sender_timeout),
+            // Bring the configured targets up to date at startup
+            pending_dumps: targets.clone(),
+            targets,
+            senders: vec![],
+            last_state: vec![],
+        })
+    }
+
+    /// The address feedback for a sender is sent to
+    fn reply_addr(&self, sender: SocketAddr) -> SocketAddr {
+        match self.reply_port {
+            Some(reply_port) => SocketAddr::new(sender.ip(), reply_port),
+            None => sender,
+        }
+    }
+
+    /// Starts sending feedback to the sender of an OSC message, beginning with every setting
+    pub fn receive_from(&mut self, sender: SocketAddr) {
+        let target = self.reply_addr(sender);
+
+        if !self.reply_to_senders || self.targets.contains(&target) {
+            return;
+        }
+
+        let now = Instant::now();
+
+        if let Some((_, last_received)) = self.senders.iter_mut().find(|(addr, _)| *addr == target) {
+            *last_received = now;
+            return;
+        }
+
+        if self.senders.len() >= MAX_SENDERS {
+            let oldest_index = self.senders
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (_, last_received))| *last_received)
+                .map(|(index, _)| index);
+
+            if let Some(oldest_index) = oldest_index {
+                let (oldest, _) = self.senders.remove(oldest_index);
+                info!("Stopped sending OSC feedback to {}", oldest);
+            }
+        }
+
+        info!("Sending OSC feedback to {}", target);
+
+        self.senders.push((target, now));
+        self.request_dump(sender);
+    }
+
+    /// Stops sending feedback to senders that haven't sent anything for a while (eg. tools that
+    /// send each message from a new port)
+    fn expire_senders(&mut self) {
+        let sender_timeout = self.sender_timeout;
+
+        self.senders.retain(|(target, last_received)| {
+            let is_active = last_received.elapsed() < sender_timeout;
+
+            if !is_active {
+                info!("Stopped sending OSC feedback to {}", target);
+            }
+
+            is_active
+        });
+    }
+
+    /// Sends every setting to the sender on the next update
+    pub fn request_dump(&mut self, sender: SocketAddr) {
+        let target = self.reply_addr(sender);
+
+        if !self.pending_dumps.contains(&target) {
+            self.pending_dumps.push(target);
+        }
+    }
+
+    /// Whether there is anyone to send feedback to
+    pub fn is_active(&self) -> bool {
+        !self.targets.is_empty() || !self.senders.is_empty() || !self.pending_dumps.is_empty()
+    }
+
+    /// Sends the settings that changed since the last update to every target and all of the
+    /// settings to the targets that requested them
+    pub fn send(&mut self, state: OscState) {
+        self.expire_senders();
+
+        for message in state.iter() {
+            let pending_dumps = &self.pending_dumps;
+
+            let targets: Box<dyn Iterator<Item = &SocketAddr>> = if self.last_state.contains(message) {
+                Box::new(pending_dumps.iter())
+            } else {
+                Box::new(
+                    self.targets
+                        .iter()
+                        .chain(self.senders.iter().map(|(target, _)| target))
+                        .filter(|target| !pending_dumps.contains(target))
+                        .chain(pending_dumps.iter())
+                )
+            };
+
+            let packet = match nannou_osc::encode(message.clone().into()) {
+                Ok(packet) => packet,
+                Err(err) => {
+                    warn!("Unable to encode OSC feedback {:?}: {:?}", message, err);
+                    continue;
+                }
+            };
+
+            for target in targets {
+                if let Err(err) = self.socket.send_to(&packet, target) {
+                    trace!("Unable to send OSC feedback to {}: {:?}", target, err);
+                }
+            }
+        }
+
+        self.pending_dumps.clear();
+        self.last_state = state;
+    }
+}
+
+/// Hue and saturation of a color as they are sent over OSC (0 to 255)
+pub fn hue_saturation(color: &crate::LedColor) -> [f32; 2] {
+    [color.hue.to_positive_degrees() / 360.0 * 255.0, color.saturation * 255.0]
+}
+
+/// The global settings and the current program's parameters as the OSC messages that set them
+pub fn state(model: &crate::Model) -> OscState {
+    use nannou_osc::Type::*;
+
+    let context = &model.context;
+    let toggle = |enabled: bool| Float(if enabled { 1.0 } else { 0.0 });
+
+    let [hue1, saturation1] = hue_saturation(&context.color);
+    let [hue2, saturation2] = hue_saturation(&context.color2);
+
+    let mut state = vec![
+        ("/variable/color1".to_string(), vec![Float(hue1), Float(saturation1)]),
+        ("/variable/color2".to_string(), vec![Float(hue2), Float(saturation2)]),
+        (
+            "/variable/globalbrightness".to_string(),
+            vec![Float(context.global_brightness_multiplier * 255.0)],
+        ),
+        ("/variable/value1".to_string(), vec![Float(context.brightness1 * 255.0)]),
+        ("/variable/value2".to_string(), vec![Float(context.brightness2 * 255.0)]),
+        ("/variable/direction".to_string(), vec![toggle(context.run_forwards)]),
+        ("/variable/interval".to_string(), vec![Float(model.fps)]),
+        // 1 while the program is stopped
+        ("/variable/stopstart".to_string(), vec![toggle(model.paused)]),
+        ("/tempo/bpm".to_string(), vec![Float(context.clock.bpm)]),
+        ("/tempo/beats_per_bar".to_string(), vec![Float(context.clock.beats_per_bar as f32)]),
+        ("/transition/type".to_string(), vec![String(model.transition.kind.name().to_string())]),
+        ("/transition/duration".to_string(), vec![Float(model.transition.duration)]),
+    ];
+
+    let program_name = model.program_exec
+        .as_ref()
+        .map(|exec| exec.info.name);
+
+    // 1 for the current program's button and 0 for the others
+    for info in crate::programs::PROGRAMS {
+        state.push((format!("/program/{}", info.name), vec![toggle(Some(info.name) == program_name)]));
+
+        if info.beat_lockable {
+            let ticks_per_beat = context.beat_locks.get(info.name).copied().unwrap_or(0.0);
+            state.push((format!("/tempo/lock/{}", info.name), vec![Float(ticks_per_beat)]));
+        }
+    }
+
+    if let Some(exec) = &model.program_exec {
+        for (param, value) in exec.params() {
+            if let Some(value) = value {
+                state.push((param.address.to_string(), vec![value.to_osc()]));
+            }
+        }
+    }
+
+    state
+}
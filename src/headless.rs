@@ -0,0 +1,34 @@
+//! Runs the model on a fixed-rate timer without opening a window
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::Model;
+
+/// Updates the model `update_rate` times per second, forever
+pub fn run(mut model: Model, update_rate: f32) -> ! {
+    let update_interval = Duration::from_secs_f32(1.0 / update_rate);
+
+    println!("Running headless at {} updates per second", update_rate);
+
+    let mut last_update = Instant::now();
+    let mut next_update = last_update + update_interval;
+
+    loop {
+        let now = Instant::now();
+
+        if next_update > now {
+            thread::sleep(next_update - now);
+        } else {
+            // Running behind. Skip the missed updates rather than trying to catch up on them.
+            next_update = now;
+        }
+
+        let now = Instant::now();
+        crate::update(&mut model, now - last_update);
+
+        last_update = now;
+        next_update += update_interval;
+    }
+}
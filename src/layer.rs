@@ -0,0 +1,216 @@
+use nannou::color::{IntoColor, Rgb, hsl};
+use eyre::{Result, eyre};
+
+use crate::program::{ProgramContext, ProgramExecutor};
+
+/// The highest layer number that can be drawn over the program
+const MAX_LAYERS: usize = 8;
+
+/// A program drawing into its own frame so that more than one program can run at once
+#[derive(Debug)]
+pub struct Layer {
+    pub exec: ProgramExecutor,
+    pub led_strips: crate::LedStripVec,
+}
+
+impl Layer {
+    pub fn new(exec: ProgramExecutor, led_strips: crate::LedStripVec) -> Self {
+        Self {
+            exec,
+            led_strips,
+        }
+    }
+
+    /// Starts the program drawing into a blank frame
+    pub fn blank(exec: ProgramExecutor, context: &ProgramContext) -> Self {
+        let led_strips = context.strip_lens
+            .iter()
+            .map(|strip_len| vec![hsl(0.0, 0.0, 0.0); *strip_len])
+            .collect();
+
+        Self::new(exec, led_strips)
+    }
+
+    /// Runs one frame of the program, drawing into the layer's frame
+    pub fn update(&mut self, context: &ProgramContext) {
+        self.exec.update(context, &mut self.led_strips);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    /// Covers the layers below
+    Normal,
+    Add,
+    Multiply,
+    /// Brightens the layers below (the inverse of multiplying their inverses)
+    Screen,
+    /// Keeps the brightest of each channel
+    Max,
+}
+
+impl BlendMode {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "normal" => Ok(BlendMode::Normal),
+            "add" => Ok(BlendMode::Add),
+            "multiply" => Ok(BlendMode::Multiply),
+            "screen" => Ok(BlendMode::Screen),
+            "max" => Ok(BlendMode::Max),
+            _ => Err(eyre!("Invalid blend mode: {:?}", name)),
+        }
+    }
+
+    /// Blends a channel (0 to 1) of the layer over the same channel of the layers below
+    fn blend_channel(&self, below: f32, layer: f32) -> f32 {
+        match self {
+            BlendMode::Normal => layer,
+            BlendMode::Add => (below + layer).min(1.0),
+            BlendMode::Multiply => below * layer,
+            BlendMode::Screen => 1.0 - (1.0 - below) * (1.0 - layer),
+            BlendMode::Max => below.max(layer),
+        }
+    }
+}
+
+/// A layer drawn over the program with its own opacity and blend mode
+#[derive(Debug)]
+pub struct StackedLayer {
+    /// `None` until a program is selected for the layer
+    layer: Option<Layer>,
+    /// Opacity from 0 to 1
+    opacity: f32,
+    blend_mode: BlendMode,
+}
+
+impl Default for StackedLayer {
+    fn default() -> Self {
+        Self {
+            layer: None,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+        }
+    }
+}
+
+/// Layers of programs composited in order over the main program
+#[derive(Debug, Default)]
+pub struct LayerStack {
+    /// Layer 1 is drawn directly over the program, layer 2 over layer 1 and so on
+    layers: Vec<StackedLayer>,
+    /// The composited frame or `None` when there are no layers to draw
+    frame: Option<crate::LedStripVec>,
+}
+
+impl LayerStack {
+    /// Runs one frame of each layer's program
+    pub fn update(&mut self, context: &ProgramContext) {
+        for stacked_layer in self.layers.iter_mut() {
+            if let Some(layer) = &mut stacked_layer.layer {
+                layer.update(context);
+            }
+        }
+    }
+
+    /// Draws the layers over the program's frame. Returns `None` if there are no layers to draw.
+    pub fn composite(&mut self, led_strips: &crate::LedStripVec) -> Option<&crate::LedStripVec> {
+        let mut active_layers = self.layers
+            .iter()
+            .filter_map(|stacked_layer| {
+                stacked_layer.layer
+                    .as_ref()
+                    .map(|layer| (layer, stacked_layer))
+            })
+            .filter(|(_, stacked_layer)| stacked_layer.opacity > 0.0)
+            .peekable();
+
+        if active_layers.peek().is_none() {
+            self.frame = None;
+            return None;
+        }
+
+        let frame = self.frame.get_or_insert_with(Vec::new);
+        frame.clone_from(led_strips);
+
+        for (layer, stacked_layer) in active_layers {
+            for (led_strip, layer_strip) in frame.iter_mut().zip(layer.led_strips.iter()) {
+                for (led_color, layer_color) in led_strip.iter_mut().zip(layer_strip.iter()) {
+                    let below = Into::<Rgb>::into(*led_color);
+                    let layer_color = Into::<Rgb>::into(*layer_color);
+
+                    let blend = |below: f32, layer: f32| {
+                        let blended = stacked_layer.blend_mode.blend_channel(below, layer);
+                        below + (blended - below) * stacked_layer.opacity
+                    };
+
+                    *led_color = Rgb::new(
+                        blend(below.red, layer_color.red),
+                        blend(below.green, layer_color.green),
+                        blend(below.blue, layer_color.blue),
+                    ).into_hsl();
+                }
+            }
+        }
+
+        self.frame.as_ref()
+    }
+
+    /// The last composited frame or `None` if there were no layers to draw
+    pub fn frame(&self) -> Option<&crate::LedStripVec> {
+        self.frame.as_ref()
+    }
+
+    pub fn receive_osc_packet<'a>(
+        &mut self,
+        layer_number: &str,
+        addr: &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+        context: &ProgramContext,
+    ) -> Result<()> {
+        use nannou_osc::Type::*;
+
+        let layer_index = layer_number
+            .parse::<usize>()
+            .ok()
+            .filter(|layer_number| (1..=MAX_LAYERS).contains(layer_number))
+            .ok_or_else(|| eyre!("Invalid layer: {:?}", layer_number))?
+            - 1;
+
+        if self.layers.len() <= layer_index {
+            self.layers.resize_with(layer_index + 1, StackedLayer::default);
+        }
+
+        let stacked_layer = &mut self.layers[layer_index];
+
+        match (addr, args) {
+            (["program", "off"], _) => {
+                stacked_layer.layer = None;
+            }
+            (["program", program_name], _) => {
+                let exec = ProgramExecutor::from_program_name(program_name, context)?;
+                stacked_layer.layer = Some(Layer::blank(exec, context));
+            }
+            (["opacity"], [
+                // Input is between 0 and 255
+                Float(opacity),
+            ]) => {
+                stacked_layer.opacity = (opacity / 255.0).clamp(0.0, 1.0);
+            }
+            (["blend"], [
+                String(blend_mode),
+            ]) => {
+                stacked_layer.blend_mode = BlendMode::from_name(blend_mode)?;
+            }
+            // Program-specific settings
+            (addr, args) => {
+                let layer = stacked_layer.layer
+                    .as_mut()
+                    .ok_or_else(|| eyre!("Layer {} has no program", layer_number))?;
+
+                layer.exec.program.receive_osc_packet(addr, args, layer.exec.frame_index)?;
+            }
+        };
+
+        Ok(())
+    }
+}
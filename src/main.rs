@@ -1,231 +1,237 @@
 #[macro_use]
 extern crate log;
 
-use std::net::UdpSocket;
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
+use eyre::{Context, Result};
 use local_ip_address::local_ip;
 use nannou::prelude::*;
 use nannou_osc::Packet;
-use program::ProgramExecutor;
-
-fn main() {
-    nannou::app(model).update(update).run();
-}
-
+use program::{ProgramContext, ProgramExecutor};
+use structopt::StructOpt;
+
+mod audio;
+mod autosave;
+mod color_correction;
+mod config;
+mod dither;
+mod feedback;
+mod headless;
+mod layer;
+mod midi;
+mod oscquery;
+mod output;
+mod outputs;
+mod power;
+mod preset;
 mod program;
 mod programs;
 mod svg_palette;
+mod tempo;
+mod topology;
+mod transition;
+mod visualizer;
+
+use autosave::Autosave;
+use config::Config;
+use feedback::Feedback;
+use layer::LayerStack;
+use midi::MidiInput;
+use oscquery::OscQueryServer;
+use output::Output;
+use power::PowerLimiter;
+use preset::PresetFade;
+use tempo::{BeatClock, TapTempo};
+use transition::{Transition, TransitionConfig, TransitionKind};
+
+#[derive(StructOpt, Debug)]
+#[structopt(about = "Controls LED strips over OSC")]
+struct Args {
+    /// Path to a TOML config file (see config.toml for an example)
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Run without opening the visualizer window (eg. on a machine without a display)
+    #[structopt(long)]
+    headless: bool,
+    /// List the programs and the OSC addresses of their parameters, then exit
+    #[structopt(long)]
+    list_programs: bool,
+}
 
-pub type LedColor = Hsl<nannou::color::encoding::Srgb>;
+fn main() -> Result<()> {
+    pretty_env_logger::init();
 
-const NUMBER_OF_LED_STRIPS: usize = 1;
-const LED_STRIP_LEN: usize = 150;
-// const LED_STRIP_LEN: usize = 4;
+    let args = Args::from_args();
 
-pub type LedStripVec = Vec<[LedColor; LED_STRIP_LEN]>;
+    if args.list_programs {
+        program::print_programs();
+        return Ok(());
+    }
 
-// Make sure this matches the `TARGET_PORT` in the `osc_sender.rs` example.
-const PORT: u16 = 8000;
+    let config = match &args.config {
+        Some(config_path) => Config::load(config_path)?,
+        None => Config::default(),
+    };
+
+    let model = Model::new(&config)?;
+
+    if args.headless {
+        headless::run(model, config.update_rate);
+    } else {
+        visualizer::run(model);
+    }
+
+    Ok(())
+}
+
+pub type LedColor = Hsl<nannou::color::encoding::Srgb>;
+
+pub type LedStripVec = Vec<Vec<LedColor>>;
 
 pub struct Model {
     pub receiver: nannou_osc::Receiver,
+    /// MIDI mapped to OSC messages and the tempo
+    pub midi: Option<MidiInput>,
+    /// The frame drawn by the program
     pub led_strips: LedStripVec,
-    pub global_brightness_multiplier: f32,
-    pub brightness1: f32,
-    pub brightness2: f32,
-    pub color: crate::LedColor,
-    pub color2: crate::LedColor,
-    pub run_forwards: bool,
+    /// Settings shared by every program (eg. the colors)
+    pub context: ProgramContext,
     pub fps: f32,
     pub fps_offset: f32,
     pub paused: bool,
+    pub tap_tempo: TapTempo,
     pub program_exec: Option<ProgramExecutor>,
-    pub led_controller_socket: UdpSocket,
+    pub transition: TransitionConfig,
+    /// Blends the previous program into the current one after switching programs
+    pub active_transition: Option<Transition>,
+    /// Fades the colors and brightnesses to a recalled preset
+    pub preset_fade: Option<PresetFade>,
+    pub presets_dir: PathBuf,
+    /// Programs drawn over the main program
+    pub layers: LayerStack,
+    pub outputs: Vec<Box<dyn Output>>,
+    pub power_limiter: Option<PowerLimiter>,
+    /// Describes the accepted OSC addresses to controllers
+    pub oscquery: Option<OscQueryServer>,
+    /// Sends the settings back to the control surfaces
+    pub feedback: Feedback,
+    /// Saves the state to restore after a restart
+    pub autosave: Option<Autosave>,
 }
 
 impl Model {
-    fn total_led_count(&self) -> usize {
-        self.led_strips
-            .iter()
-            .fold(0, |sum, led_strip| sum + led_strip.len())
-    }
+    fn new(config: &Config) -> Result<Self> {
+        // Bind an `osc::Receiver` to a port.
+        let receiver = nannou_osc::receiver(config.osc_port)
+            .wrap_err_with(|| format!("Unable to listen for OSC on port {}", config.osc_port))?;
+
+        let outputs = output::outputs_from_config(config)?;
+
+        println!(
+            "LED strip lengths: {:?}",
+            config.topology.strips.iter().map(|strip| strip.len).collect::<Vec<_>>(),
+        );
+
+        // Build the model
+        let led_strips = config.topology.led_strips();
+
+        let mut model = Model {
+            receiver,
+            midi: MidiInput::open(&config.midi)?,
+            led_strips,
+            context: ProgramContext {
+                global_brightness_multiplier: config.global_brightness,
+                brightness1: config.brightness1,
+                brightness2: config.brightness2,
+                color: config.color1(),
+                color2: config.color2(),
+                run_forwards: true,
+                strip_lens: config.topology.strips.iter().map(|strip| strip.len).collect(),
+                audio_file: config.audio_file.clone(),
+                playlist_file: config.playlist.clone(),
+                clock: BeatClock::new(&config.tempo),
+                beat_locks: config.tempo.lock.clone(),
+            },
+            fps: config.fps,
+            fps_offset: 0.0,
+            paused: false,
+            tap_tempo: TapTempo::default(),
+            program_exec: None,
+            transition: config.transition,
+            active_transition: None,
+            preset_fade: None,
+            presets_dir: config.presets_dir.clone(),
+            layers: LayerStack::default(),
+            outputs,
+            power_limiter: config.power
+                .as_ref()
+                .map(|power| PowerLimiter::new(power, config.topology.strips.len())),
+            oscquery: config.oscquery_port
+                .map(|http_port| OscQueryServer::start(http_port, config.osc_port))
+                .transpose()?,
+            feedback: Feedback::new(&config.feedback)?,
+            autosave: None,
+        };
 
-    fn all_leds_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut crate::LedColor)> {
-        self.led_strips
-            .iter_mut()
-            .flat_map(|led_strip| led_strip.iter_mut())
-            .enumerate()
-    }
-}
+        model.program_exec = Some(
+            ProgramExecutor::from_program_name(&config.program, &model.context)
+                .wrap_err("Invalid startup program")?
+        );
 
-fn model(app: &App) -> Model {
-    pretty_env_logger::init();
+        // Pick up where the last run left off
+        let autosave = config.state_file
+            .clone()
+            .map(|file_path| Autosave::new(file_path, config.autosave_interval));
 
-    // Configure the window
-    app.new_window()
-        .title("OSC Receiver")
-        .size(1440, 550)
-        // .raw_event(raw_window_event)
-        .view(view)
-        .build()
-        .unwrap();
-
-    // Bind an `osc::Receiver` to a port.
-    let receiver = nannou_osc::receiver(PORT).unwrap();
-
-    // This port number is arbitrary
-    let led_controller_socket = UdpSocket::bind("0.0.0.0:49781")
-        .expect("couldn't create open port 49783 for LED controller connection");
-
-    let led_controller_addr =
-        std::env::var("LED_CONTROLLER").expect("LED_CONTROLLER should be a valid host and port");
-
-    led_controller_socket
-        .connect(&led_controller_addr)
-        .expect(&format!(
-            "Connecting to LED Controller at: {:?}",
-            &led_controller_addr
-        ));
-
-    println!("Connected to LED Controller at: {:?}", &led_controller_addr);
-
-    // Build the model
-    let led_strips = vec![[crate::LedColor::default(); LED_STRIP_LEN]; NUMBER_OF_LED_STRIPS];
-
-    #[allow(unused_mut)]
-    let mut model = Model {
-        receiver,
-        led_strips,
-        global_brightness_multiplier: 1.0,
-        brightness1: 0.5,
-        brightness2: 0.5,
-        color: nannou::color::rgb(1.0, 0.0, 0.0).into(),
-        color2: nannou::color::rgb(0.0, 0.0, 0.0).into(),
-        run_forwards: true,
-        fps: 40.0,
-        fps_offset: 0.0,
-        paused: false,
-        // program: None,
-        program_exec: None,
-        led_controller_socket,
-    };
+        if let Some(autosave) = &autosave {
+            if let Err(err) = autosave.restore(&mut model) {
+                println!("{:?}", err.wrap_err("Unable to restore the saved state"));
+            }
+        }
 
-    model.program_exec = Some(ProgramExecutor::new(programs::Blink::new(&model).unwrap()));
+        model.autosave = autosave;
 
-    // Print the local ip address
-    if let Ok(ip_address) = local_ip() {
-        println!("Listening for OSC packets at {}:{}\n", ip_address, PORT);
-    } else {
-        println!("Listening for OSC packets on port {}\n", PORT);
-    }
+        // Print the local ip address
+        if let Ok(ip_address) = local_ip() {
+            println!("Listening for OSC packets at {}:{}\n", ip_address, config.osc_port);
+        } else {
+            println!("Listening for OSC packets on port {}\n", config.osc_port);
+        }
 
-    // Return the model
-    model
+        if let Some(http_port) = config.oscquery_port {
+            println!("Serving the OSC addresses (OSCQuery) at http://localhost:{}/\n", http_port);
+        }
+
+        Ok(model)
+    }
 }
 
-// fn raw_window_event(app: &App, model: &mut Model, event: &ui::RawWindowEvent) {
-//     model.ui.handle_raw_event(app, event);
-// }
+pub fn update(model: &mut Model, since_last: Duration) {
+    // The beat keeps time even while the program is stopped
+    model.context.clock.advance(since_last);
 
-fn update(_app: &App, model: &mut Model, update: Update) {
     // Receive any pending osc packets.
-    for (packet, _) in model.receiver.try_iter() {
-        // println!("Received OSC packet: {:?}", packet);
-        use nannou_osc::{Message, Type::*};
-
-        let empty_args = vec![];
-
-        let (addr, args) = match &packet {
-            Packet::Message(Message { addr, args }) => (
-                addr.trim_start_matches('/').split('/').collect::<Vec<_>>(),
-                &args.as_ref().unwrap_or(&empty_args)[..],
-            ),
-            _ => {
-                println!("Unsupported packet received: {:?}", packet);
-                continue;
-            }
-        };
+    let packets = model.receiver.try_iter().collect::<Vec<_>>();
 
-        // Update settings based on the OSC message
-        match (&addr[..], args) {
-            // Hue and Saturation
-            (["variable", "color1"], [
-                Float(hue),
-                Float(saturation),
-            ]) => {
-                model.color = hsl(hue / 255.0, saturation / 255.0, model.color.lightness);
-            }
-            (["variable", "color2"], [
-                Float(hue),
-                Float(saturation),
-            ]) => {
-                model.color2 = hsl(hue / 255.0, saturation / 255.0, model.color2.lightness);
-            }
-            // Brightness
-            (["variable", "globalbrightness"], [
-                Float(global_brightness),
-            ]) => {
-                model.global_brightness_multiplier = global_brightness / 255.0;
-                model.color.lightness = model.brightness1 * model.global_brightness_multiplier;
-                model.color2.lightness = model.brightness2 * model.global_brightness_multiplier;
-            }
-            (["variable", "value1"], [
-                Float(lightness),
-            ]) => {
-                model.brightness1 = lightness / 255.0;
-                model.color.lightness = model.brightness1 * model.global_brightness_multiplier;
-            }
-            (["variable", "value2"], [
-                Float(lightness),
-            ]) => {
-                model.brightness2 = lightness / 255.0;
-                model.color2.lightness = model.brightness2 * model.global_brightness_multiplier;
-            }
-            // Direction
-            (["variable", "direction"], [
-                // Input is between 0 and 255
-                Float(input),
-            ]) => {
-                model.run_forwards = input.to_u8() == Some(1u8);
-            }
-            // Speed
-            (["variable", "interval"], [
-                // Input is between 0 and 255
-                Float(input),
-            ]) => {
-                model.fps = *input;
-            }
-            (["variable", "stopstart"], _) => {
-                model.paused = !model.paused;
-            }
-            // Program selection
-            (["program", program_name], _) => {
-                match ProgramExecutor::from_program_name(program_name, &model) {
-                    Ok(program) => model.program_exec = Some(program),
-                    Err(err) => println!("{:?}", err),
-                }
-            }
-            (["1", "push1"], _) => {
-                match ProgramExecutor::from_program_name("on", &model) {
-                    Ok(program) => model.program_exec = Some(program),
-                    Err(err) => println!("{:?}", err),
-                }
-            }
-            // Other settings
-            (addr, args) => {
-                // Program-specific settings
-                if let Err(err) = model.program_exec
-                    .as_mut()
-                    .map(|exec| exec.program.receive_osc_packet(
-                        addr,
-                        args,
-                        exec.frame_index,
-                    ))
-                    .transpose()
-                {
-                    println!("{:?}", err);
-                }
-            }
+    for (packet, sender) in packets {
+        model.feedback.receive_from(sender);
+        receive_osc_packet(model, &packet, Some(sender));
+    }
+
+    // MIDI is handled the same as the OSC messages it is mapped to
+    let midi_packets = match &mut model.midi {
+        Some(midi) => midi.receive(&mut model.context.clock),
+        None => vec![],
+    };
+
+    for packet in midi_packets {
+        receive_osc_packet(model, &packet, None);
+    }
+
+    // Presets fade in real time, even while the program is stopped
+    if let Some(preset_fade) = &mut model.preset_fade {
+        if preset_fade.update(&mut model.context, since_last) {
+            model.preset_fade = None;
         }
     }
 
@@ -237,102 +243,287 @@ fn update(_app: &App, model: &mut Model, update: Update) {
             model.fps_offset = model.fps_offset % 40.0;
 
             for _ in 0..frames {
-                exec.update(model);
+                match model.active_transition.take() {
+                    Some(mut transition) => {
+                        if !transition.update(&mut exec, &model.context, &mut model.led_strips) {
+                            model.active_transition = Some(transition);
+                        }
+                    }
+                    None => exec.update(&model.context, &mut model.led_strips),
+                }
+
+                model.layers.update(&model.context);
             }
 
-            use rosc::{OscColor, OscMessage, OscPacket, OscType};
-
-            let led_control_packet = OscPacket::Message(OscMessage {
-                addr: "/led_strips/0".to_string(),
-                args: model
-                    .all_leds_mut()
-                    .map(|(_, led)| {
-                        let rgba = Into::<Rgba>::into(*led);
-                        OscType::Color(OscColor {
-                            red: (rgba.red * 255.0) as u8,
-                            blue: (rgba.blue * 255.0) as u8,
-                            green: (rgba.green * 255.0) as u8,
-                            alpha: 1.0 as u8,
-                        })
-                    })
-                    .collect(),
-            });
-
-            let packet_buf = rosc::encoder::encode(&led_control_packet).unwrap();
-
-            if let Err(err) = model.led_controller_socket.send(&packet_buf) {
-                warn!("Failed to send UDP packet to LED controller");
-                trace!("UDP Error: {:?}", err);
-            } else {
-                let since_last = update.since_last.as_millis();
-                trace!(
-                    "UDP packet sent! ({} bytes, {:?} fps)",
-                    packet_buf.len(),
-                    if since_last > 0 { 1000 / since_last } else { 0 },
-                );
+            // Draw the layers over the program
+            let led_strips = model.layers
+                .composite(&model.led_strips)
+                .unwrap_or(&model.led_strips);
+
+            // Scale down the frame sent to the outputs if it would draw too much power
+            let limited_strips = match &mut model.power_limiter {
+                Some(power_limiter) => {
+                    let corrections = model.outputs
+                        .iter()
+                        .filter_map(|output| output.correction())
+                        .collect::<Vec<_>>();
+
+                    power_limiter.limit(led_strips, &corrections)
+                }
+                None => None,
+            };
+
+            let led_strips = limited_strips.as_ref().unwrap_or(led_strips);
+
+            for output in model.outputs.iter_mut() {
+                if let Err(err) = output.send(led_strips) {
+                    warn!("{:?}", err);
+                }
             }
 
+            let since_last = since_last.as_millis();
+            trace!(
+                "Frame sent to {} output(s) ({:?} fps)",
+                model.outputs.len(),
+                1000u128.checked_div(since_last).unwrap_or(0),
+            );
+
             model.program_exec = Some(exec);
         }
     }
+
+    if let Some(oscquery) = &model.oscquery {
+        oscquery.update(model);
+    }
+
+    if model.feedback.is_active() {
+        let state = feedback::state(model);
+        model.feedback.send(state);
+    }
+
+    if let Some(mut autosave) = model.autosave.take() {
+        autosave.update(model);
+        model.autosave = Some(autosave);
+    }
+}
+
+/// Transitions from the current program to a new one
+fn switch_program(model: &mut Model, program_name: &str) -> Result<()> {
+    let program = ProgramExecutor::from_program_name(program_name, &model.context)?;
+
+    model.active_transition = model.program_exec
+        .replace(program)
+        .and_then(|outgoing| Transition::new(model.transition, outgoing, &model.led_strips));
+
+    Ok(())
 }
 
-const PAGE_MARGIN: f32 = 6.0;
-
-fn view(app: &App, model: &Model, frame: Frame) {
-    // get canvas to draw on
-    let draw = app.draw();
-
-    let win_rec = app.main_window().rect();
-    let origin_x = win_rec.left() + PAGE_MARGIN;
-    let origin_y = win_rec.top() - PAGE_MARGIN;
-
-    // set background to blue
-    draw.background().color(nannou::color::BLACK);
-
-    const LED_BOX_SIZE: f32 = 8.0;
-    const LED_BORDER_SIZE: f32 = 0.5;
-    const STROKE_WEIGHT: f32 = 0.5;
-    const TEXT_HEIGHT: u32 = 14;
-
-    for (strip_index, led_strip) in model.led_strips.iter().enumerate() {
-        let offset_y = origin_y - strip_index as f32 * 32.0;
-
-        let text = format!("LED STRIP #{}", strip_index);
-        draw.text(&text)
-            .color(WHITE)
-            .font_size(TEXT_HEIGHT)
-            .y(offset_y - (TEXT_HEIGHT as f32) / 2.0)
-            .wh(win_rec.wh());
-
-        for (led_index, led_color) in led_strip.iter().enumerate() {
-            let x = origin_x + (LED_BOX_SIZE + LED_BORDER_SIZE * 2.0) * (led_index as f32 + 0.5);
-            let y = offset_y
-                - (TEXT_HEIGHT as f32)
-                - PAGE_MARGIN
-                - (LED_BOX_SIZE + LED_BORDER_SIZE * 2.0) * 0.5;
-
-            draw.rect()
-                .x(x)
-                .y(y)
-                .w_h(
-                    LED_BOX_SIZE + LED_BORDER_SIZE * 2.0,
-                    LED_BOX_SIZE + LED_BORDER_SIZE * 2.0,
-                )
-                .stroke(gray(0.7))
-                .stroke_weight(STROKE_WEIGHT)
-                .color(BLACK);
-            // .hsv(1.0, 1.0, 1.0);
-
-            draw.rect()
-                .x(x - STROKE_WEIGHT / 2.0)
-                .y(y)
-                .w_h(LED_BOX_SIZE, LED_BOX_SIZE)
-                .color(*led_color);
-            // .hsv(1.0, 1.0, 1.0);
+/// `sender` is `None` for messages mapped from MIDI
+fn receive_osc_packet(model: &mut Model, packet: &Packet, sender: Option<SocketAddr>) {
+    // println!("Received OSC packet: {:?}", packet);
+    use nannou_osc::{Message, Type::*};
+
+    let empty_args = vec![];
+
+    let (addr, args) = match packet {
+        Packet::Message(Message { addr, args }) => (
+            addr.trim_start_matches('/').split('/').collect::<Vec<_>>(),
+            &args.as_ref().unwrap_or(&empty_args)[..],
+        ),
+        _ => {
+            println!("Unsupported packet received: {:?}", packet);
+            return;
         }
-    }
+    };
 
-    // put everything on the frame
-    draw.to_frame(app, &frame).unwrap();
+    // Update settings based on the OSC message
+    match (&addr[..], args) {
+        // Hue and Saturation
+        (["variable", "color1"], [
+            Float(hue),
+            Float(saturation),
+        ]) => {
+            let context = &mut model.context;
+            context.color = hsl(hue / 255.0, saturation / 255.0, context.color.lightness);
+        }
+        (["variable", "color2"], [
+            Float(hue),
+            Float(saturation),
+        ]) => {
+            let context = &mut model.context;
+            context.color2 = hsl(hue / 255.0, saturation / 255.0, context.color2.lightness);
+        }
+        // Brightness
+        (["variable", "globalbrightness"], [
+            Float(global_brightness),
+        ]) => {
+            let context = &mut model.context;
+            context.global_brightness_multiplier = global_brightness / 255.0;
+            context.color.lightness = context.brightness1 * context.global_brightness_multiplier;
+            context.color2.lightness = context.brightness2 * context.global_brightness_multiplier;
+        }
+        (["variable", "value1"], [
+            Float(lightness),
+        ]) => {
+            let context = &mut model.context;
+            context.brightness1 = lightness / 255.0;
+            context.color.lightness = context.brightness1 * context.global_brightness_multiplier;
+        }
+        (["variable", "value2"], [
+            Float(lightness),
+        ]) => {
+            let context = &mut model.context;
+            context.brightness2 = lightness / 255.0;
+            context.color2.lightness = context.brightness2 * context.global_brightness_multiplier;
+        }
+        // Direction
+        (["variable", "direction"], [
+            // Input is between 0 and 255
+            Float(input),
+        ]) => {
+            model.context.run_forwards = input.to_u8() == Some(1u8);
+        }
+        // Speed
+        (["variable", "interval"], [
+            // Input is between 0 and 255
+            Float(input),
+        ]) => {
+            model.fps = *input;
+        }
+        // 1 stops the program and 0 runs it
+        (["variable", "stopstart"], [
+            Float(stopped),
+        ]) => {
+            model.paused = *stopped != 0.0;
+        }
+        (["variable", "stopstart"], [
+            Int(stopped),
+        ]) => {
+            model.paused = *stopped != 0;
+        }
+        // Pauses or resumes the program (eg. from a MIDI note)
+        (["variable", "stopstart"], []) => {
+            model.paused = !model.paused;
+        }
+        // Tempo
+        (["tempo", "tap"], args) => {
+            // Ignore buttons being released
+            if !matches!(args, [Float(value)] if *value == 0.0) {
+                model.tap_tempo.tap(&mut model.context.clock);
+            }
+        }
+        (["tempo", "bpm"], [
+            Float(bpm),
+        ]) => {
+            if let Err(err) = model.context.clock.set_bpm(*bpm) {
+                println!("{:?}", err);
+            }
+        }
+        (["tempo", "beats_per_bar"], [
+            Float(beats_per_bar),
+        ]) => {
+            if let Err(err) = model.context.clock.set_beats_per_bar(*beats_per_bar) {
+                println!("{:?}", err);
+            }
+        }
+        (["tempo", "lock", program_name], [
+            // Frames per beat, 0 to unlock
+            Float(ticks_per_beat),
+        ]) => {
+            let beat_locks = &mut model.context.beat_locks;
+
+            if let Err(err) = tempo::set_beat_lock(beat_locks, program_name, *ticks_per_beat) {
+                println!("{:?}", err);
+            }
+        }
+        // Program selection
+        (["program", program_name], _) => {
+            if let Err(err) = switch_program(model, program_name) {
+                println!("{:?}", err);
+            }
+        }
+        (["transition", "type"], [
+            String(kind),
+        ]) => {
+            match TransitionKind::from_name(kind) {
+                Ok(kind) => model.transition.kind = kind,
+                Err(err) => println!("{:?}", err),
+            }
+        }
+        (["transition", "duration"], [
+            Float(seconds),
+        ]) => {
+            let transition = TransitionConfig { duration: *seconds, ..model.transition };
+
+            match transition.validate() {
+                Ok(()) => model.transition = transition,
+                Err(err) => println!("{:?}", err),
+            }
+        }
+        // Presets
+        (["preset", "save", number], _) => {
+            if let Err(err) = preset::save(model, number) {
+                println!("{:?}", err);
+            }
+        }
+        (["preset", "recall", number], args) => {
+            // Optionally fades to the preset over a number of seconds
+            let seconds = match args {
+                [Float(seconds)] => *seconds,
+                _ => 0.0,
+            };
+
+            if let Err(err) = preset::recall(model, number, seconds) {
+                println!("{:?}", err);
+            }
+        }
+        // Sends every setting back to the sender
+        (["feedback", "dump"], _) => {
+            if let Some(sender) = sender {
+                model.feedback.request_dump(sender);
+            }
+        }
+        (["1", "push1"], _) => {
+            if let Err(err) = switch_program(model, "on") {
+                println!("{:?}", err);
+            }
+        }
+        // Layers drawn over the program
+        (["layer", layer_number, addr @ ..], args) => {
+            if let Err(err) = model.layers.receive_osc_packet(layer_number, addr, args, &model.context) {
+                println!("{:?}", err);
+            }
+        }
+        // Output settings (eg. color correction)
+        (["output", output_index, addr @ ..], args) => {
+            let output = output_index
+                .parse::<usize>()
+                .ok()
+                .and_then(|output_index| model.outputs.get_mut(output_index));
+
+            match output {
+                Some(output) => {
+                    if let Err(err) = output.receive_osc_packet(addr, args) {
+                        println!("{:?}", err);
+                    }
+                }
+                None => println!("Invalid output: {:?}", output_index),
+            }
+        }
+        // Other settings
+        (addr, args) => {
+            // Program-specific settings
+            if let Err(err) = model.program_exec
+                .as_mut()
+                .map(|exec| exec.program.receive_osc_packet(
+                    addr,
+                    args,
+                    exec.frame_index,
+                ))
+                .transpose()
+            {
+                println!("{:?}", err);
+            }
+        }
+    }
 }
@@ -0,0 +1,428 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Instant,
+};
+
+use serde::Deserialize;
+use eyre::{Context, Result, eyre};
+
+use crate::tempo::{BeatClock, MidiClock};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct MidiConfig {
+    /// File or pipe that raw MIDI bytes are read from (eg. a FIFO or a raw MIDI device such as
+    /// "/dev/snd/midiC1D0"), or "-" for stdin. MIDI input is disabled if unset.
+    pub input: Option<PathBuf>,
+    /// MIDI channel (1 to 16) to listen on. Listens on every channel if unset.
+    pub channel: Option<u8>,
+    /// Follow the tempo of MIDI clock
+    pub clock: bool,
+    /// Control changes sent as OSC messages
+    pub cc: Vec<CcMapping>,
+    /// Notes sent as OSC messages
+    pub notes: Vec<NoteMapping>,
+}
+
+/// Sends a control change to an OSC address as a single float
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CcMapping {
+    pub controller: u8,
+    /// eg. "/variable/globalbrightness"
+    pub address: String,
+    /// The value sent for a CC value of 0
+    #[serde(default)]
+    pub min: f32,
+    /// The value sent for a CC value of 127
+    #[serde(default = "default_cc_max")]
+    pub max: f32,
+}
+
+/// Sends an OSC message without any arguments when a note is played
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NoteMapping {
+    pub note: u8,
+    /// eg. "/program/pulse"
+    pub address: String,
+}
+
+fn default_cc_max() -> f32 {
+    255.0
+}
+
+impl Default for MidiConfig {
+    fn default() -> Self {
+        let cc = |controller: u8, address: &str| CcMapping {
+            controller,
+            address: address.to_string(),
+            min: 0.0,
+            max: default_cc_max(),
+        };
+
+        let note = |note: u8, address: &str| NoteMapping {
+            note,
+            address: address.to_string(),
+        };
+
+        // Pads from C1 (36) upwards: the "on" flash, stop/start and tap tempo, then the programs
+        // from C2 (48)
+        let mut notes = vec![
+            note(36, "/1/push1"),
+            note(37, "/variable/stopstart"),
+            note(38, "/tempo/tap"),
+        ];
+
+        notes.extend(crate::programs::PROGRAMS
+            .iter()
+            .enumerate()
+            .map(|(program_index, info)| note(48 + program_index as u8, &format!("/program/{}", info.name))));
+
+        Self {
+            input: None,
+            channel: None,
+            clock: true,
+            cc: vec![
+                cc(7, "/variable/globalbrightness"),
+                cc(1, "/variable/value1"),
+                cc(2, "/variable/value2"),
+                cc(3, "/variable/interval"),
+            ],
+            notes,
+        }
+    }
+}
+
+impl MidiConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(channel) = self.channel {
+            if !(1..=16).contains(&channel) {
+                return Err(eyre!("channel must be between 1 and 16, got: {}", channel));
+            }
+        }
+
+        let addresses = self.cc
+            .iter()
+            .map(|mapping| (mapping.controller, &mapping.address))
+            .chain(self.notes.iter().map(|mapping| (mapping.note, &mapping.address)));
+
+        for (number, address) in addresses {
+            if number > 127 {
+                return Err(eyre!("MIDI controller and note numbers must be 0 to 127, got: {}", number));
+            }
+
+            if !address.starts_with('/') {
+                return Err(eyre!("OSC address must start with a '/', got: {:?}", address));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiMessage {
+    /// Channels are numbered from 0
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    /// Sent 24 times per beat
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+/// Parses a raw MIDI byte stream, including running status and realtime messages sent in the
+/// middle of other messages
+#[derive(Debug, Default)]
+pub struct MidiParser {
+    /// The status byte of the current channel message
+    running_status: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl MidiParser {
+    /// Adds a byte to the current message. Returns the message once it is complete.
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        match byte {
+            // System realtime messages can appear anywhere, even between the bytes of a message
+            0xf8..=0xff => match byte {
+                0xf8 => Some(MidiMessage::Clock),
+                0xfa => Some(MidiMessage::Start),
+                0xfb => Some(MidiMessage::Continue),
+                0xfc => Some(MidiMessage::Stop),
+                _ => None,
+            },
+            // System common messages (including sysex) cancel the running status. Their data bytes
+            // are ignored until the next status byte.
+            0xf0..=0xf7 => {
+                self.running_status = None;
+                self.data.clear();
+                None
+            }
+            // Channel message status
+            0x80..=0xef => {
+                self.running_status = Some(byte);
+                self.data.clear();
+                None
+            }
+            // Data
+            _ => {
+                let status = self.running_status?;
+                self.data.push(byte);
+
+                let data_len = match status & 0xf0 {
+                    // Program change and channel pressure
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+
+                if self.data.len() < data_len {
+                    return None;
+                }
+
+                let channel = status & 0x0f;
+                let data = std::mem::take(&mut self.data);
+
+                match (status & 0xf0, &data[..]) {
+                    (0x90, [note, 0]) | (0x80, [note, _]) => Some(MidiMessage::NoteOff {
+                        channel,
+                        note: *note,
+                    }),
+                    (0x90, [note, velocity]) => Some(MidiMessage::NoteOn {
+                        channel,
+                        note: *note,
+                        velocity: *velocity,
+                    }),
+                    (0xb0, [controller, value]) => Some(MidiMessage::ControlChange {
+                        channel,
+                        controller: *controller,
+                        value: *value,
+                    }),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// Receives MIDI from a byte stream and maps it to OSC messages and the tempo
+#[derive(Debug)]
+pub struct MidiInput {
+    /// Messages along with the time they were received
+    messages: Receiver<(Instant, MidiMessage)>,
+    config: MidiConfig,
+    clock: MidiClock,
+}
+
+impl MidiInput {
+    /// Opens the configured input, or returns `None` if MIDI input is disabled
+    pub fn open(config: &MidiConfig) -> Result<Option<Self>> {
+        let input = match &config.input {
+            Some(input) => input.clone(),
+            None => return Ok(None),
+        };
+
+        println!("Receiving MIDI from {:?}", input);
+
+        if input == Path::new("-") {
+            return Ok(Some(Self::from_reader(io::stdin(), config.clone())));
+        }
+
+        // Checked here so that a missing input is reported at startup
+        fs::metadata(&input)
+            .wrap_err_with(|| format!("Unable to open MIDI input: {:?}", input))?;
+
+        // Opening a FIFO blocks until something starts writing to it, so the file is opened by
+        // the reader thread
+        let open = move || {
+            File::open(&input).wrap_err_with(|| format!("Unable to open MIDI input: {:?}", input))
+        };
+
+        Ok(Some(Self::spawn_reader(open, config.clone())))
+    }
+
+    /// Reads raw MIDI bytes from `reader` on a background thread
+    pub fn from_reader(reader: impl Read + Send + 'static, config: MidiConfig) -> Self {
+        Self::spawn_reader(move || Ok(reader), config)
+    }
+
+    /// Opens the reader and reads raw MIDI bytes from it on a background thread
+    fn spawn_reader<R: Read>(
+        open: impl FnOnce() -> Result<R> + Send + 'static,
+        config: MidiConfig,
+    ) -> Self {
+        let (sender, messages) = mpsc::channel();
+
+        thread::spawn(move || {
+            let reader = match open() {
+                Ok(reader) => reader,
+                Err(err) => {
+                    println!("{:?}", err);
+                    return;
+                }
+            };
+
+            let mut parser = MidiParser::default();
+
+            for byte in BufReader::new(reader).bytes() {
+                let byte = match byte {
+                    Ok(byte) => byte,
+                    Err(err) => {
+                        warn!("Unable to read MIDI input: {:?}", err);
+                        break;
+                    }
+                };
+
+                if let Some(message) = parser.push(byte) {
+                    if sender.send((Instant::now(), message)).is_err() {
+                        break;
+                    }
+                }
+            }
+
+            info!("MIDI input ended");
+        });
+
+        Self {
+            messages,
+            config,
+            clock: MidiClock::default(),
+        }
+    }
+
+    /// Follows the MIDI clock and returns the OSC packets that the other pending messages are
+    /// mapped to
+    pub fn receive(&mut self, clock: &mut BeatClock) -> Vec<nannou_osc::Packet> {
+        use nannou_osc::Type::Float;
+
+        let mut packets = vec![];
+
+        for (received_at, message) in self.messages.try_iter() {
+            trace!("MIDI received: {:?}", message);
+
+            let channel = match message {
+                MidiMessage::NoteOn { channel, .. }
+                | MidiMessage::NoteOff { channel, .. }
+                | MidiMessage::ControlChange { channel, .. } => Some(channel + 1),
+                _ => None,
+            };
+
+            if self.config.channel.is_some() && channel.is_some() && channel != self.config.channel {
+                continue;
+            }
+
+            match message {
+                MidiMessage::ControlChange { controller, value, .. } => {
+                    let mappings = self.config.cc
+                        .iter()
+                        .filter(|mapping| mapping.controller == controller);
+
+                    for mapping in mappings {
+                        let value = mapping.min + (mapping.max - mapping.min) * value as f32 / 127.0;
+                        packets.push((mapping.address.clone(), vec![Float(value)]).into());
+                    }
+                }
+                MidiMessage::NoteOn { note, .. } => {
+                    let mappings = self.config.notes
+                        .iter()
+                        .filter(|mapping| mapping.note == note);
+
+                    for mapping in mappings {
+                        packets.push((mapping.address.clone(), vec![]).into());
+                    }
+                }
+                MidiMessage::Clock if self.config.clock => self.clock.tick(received_at, clock),
+                MidiMessage::Start if self.config.clock => self.clock.start(clock),
+                _ => {}
+            }
+        }
+
+        packets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<MidiMessage> {
+        let mut parser = MidiParser::default();
+        bytes.iter().filter_map(|byte| parser.push(*byte)).collect()
+    }
+
+    #[test]
+    fn running_status() {
+        assert_eq!(parse(&[0xb1, 7, 100, 7, 50, 1, 0]), vec![
+            MidiMessage::ControlChange { channel: 1, controller: 7, value: 100 },
+            MidiMessage::ControlChange { channel: 1, controller: 7, value: 50 },
+            MidiMessage::ControlChange { channel: 1, controller: 1, value: 0 },
+        ]);
+    }
+
+    #[test]
+    fn realtime_inside_a_message() {
+        assert_eq!(parse(&[0x90, 0xf8, 36, 0xf8, 100]), vec![
+            MidiMessage::Clock,
+            MidiMessage::Clock,
+            MidiMessage::NoteOn { channel: 0, note: 36, velocity: 100 },
+        ]);
+    }
+
+    #[test]
+    fn note_on_with_zero_velocity_is_note_off() {
+        assert_eq!(parse(&[0x92, 36, 100, 36, 0]), vec![
+            MidiMessage::NoteOn { channel: 2, note: 36, velocity: 100 },
+            MidiMessage::NoteOff { channel: 2, note: 36 },
+        ]);
+    }
+
+    #[test]
+    fn sysex_cancels_running_status() {
+        // The data bytes of the sysex and those after it are ignored until the next status byte
+        assert_eq!(parse(&[0x90, 36, 100, 0xf0, 0x7e, 36, 0xf7, 37, 100, 0x90, 38, 100]), vec![
+            MidiMessage::NoteOn { channel: 0, note: 36, velocity: 100 },
+            MidiMessage::NoteOn { channel: 0, note: 38, velocity: 100 },
+        ]);
+    }
+
+    #[test]
+    fn maps_a_byte_stream_to_osc() {
+        // CC 7 at full scale and note 38 on channel 1
+        let bytes: &[u8] = &[0xb0, 7, 127, 0x90, 38, 100];
+        let mut input = MidiInput::from_reader(bytes, MidiConfig::default());
+        let mut clock = BeatClock::default();
+
+        let started = Instant::now();
+        let mut packets = vec![];
+
+        while packets.len() < 2 && started.elapsed() < Duration::from_secs(5) {
+            packets.extend(input.receive(&mut clock));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let messages = packets
+            .into_iter()
+            .map(|packet| match packet {
+                nannou_osc::Packet::Message(message) => (message.addr, message.args),
+                packet => panic!("Unexpected packet: {:?}", packet),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(messages, vec![
+            ("/variable/globalbrightness".to_string(), Some(vec![nannou_osc::Type::Float(255.0)])),
+            ("/tempo/tap".to_string(), Some(vec![])),
+        ]);
+    }
+}
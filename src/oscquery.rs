@@ -0,0 +1,337 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use eyre::{Context, Result, eyre};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    feedback,
+    program::{ParamKind, ParamValue},
+    transition::TransitionKind,
+};
+
+/// Access values of OSCQuery nodes
+const ACCESS_WRITE: u8 = 2;
+const ACCESS_READ_WRITE: u8 = 3;
+
+/// Serves an OSCQuery-style description of the OSC addresses that are accepted over HTTP so that
+/// controllers (eg. TouchOSC or Open Stage Control) can discover them.
+///
+/// `GET /` returns the whole namespace, `GET /<address>` a single node, `GET /<address>?VALUE` a
+/// single attribute of the node and `GET /?HOST_INFO` the OSC port.
+#[derive(Debug)]
+pub struct OscQueryServer {
+    /// The namespace as of the last update
+    namespace: Arc<Mutex<Value>>,
+}
+
+impl OscQueryServer {
+    pub fn start(http_port: u16, osc_port: u16) -> Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", http_port))
+            .wrap_err_with(|| format!("Unable to listen for OSCQuery requests on port {}", http_port))?;
+
+        let namespace = Arc::new(Mutex::new(json!({ "FULL_PATH": "/" })));
+
+        let host_info = json!({
+            "NAME": "nannou-led-controller",
+            "OSC_PORT": osc_port,
+            "OSC_TRANSPORT": "UDP",
+            "EXTENSIONS": {
+                "ACCESS": true,
+                "VALUE": true,
+                "RANGE": true,
+                "DESCRIPTION": true,
+            },
+        });
+
+        let server_namespace = Arc::clone(&namespace);
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream
+                    .map_err(|err| eyre!(err))
+                    .and_then(|stream| respond(stream, &server_namespace, &host_info));
+
+                if let Err(err) = result {
+                    warn!("OSCQuery request failed: {:?}", err);
+                }
+            }
+        });
+
+        Ok(Self { namespace })
+    }
+
+    /// Publishes the current values of the global and program parameters
+    pub fn update(&self, model: &crate::Model) {
+        let namespace = namespace(model);
+
+        if let Ok(mut current) = self.namespace.lock() {
+            *current = namespace;
+        }
+    }
+}
+
+/// Describes the global settings and the current program's parameters
+fn namespace(model: &crate::Model) -> Value {
+    let context = &model.context;
+    let mut root = json!({ "FULL_PATH": "/" });
+
+    let color_node = |description: &str, color: &crate::LedColor| json!({
+        "TYPE": "ff",
+        "VALUE": feedback::hue_saturation(color),
+        "RANGE": [{ "MIN": 0.0, "MAX": 255.0 }, { "MIN": 0.0, "MAX": 255.0 }],
+        "ACCESS": ACCESS_READ_WRITE,
+        "DESCRIPTION": description,
+    });
+
+    insert(&mut root, "/variable/color1", color_node("Hue and saturation of color 1", &context.color));
+    insert(&mut root, "/variable/color2", color_node("Hue and saturation of color 2", &context.color2));
+
+    let globals = [
+        (
+            "/variable/globalbrightness",
+            "Brightness of every program",
+            context.global_brightness_multiplier * 255.0,
+            255.0,
+        ),
+        ("/variable/value1", "Brightness of color 1", context.brightness1 * 255.0, 255.0),
+        ("/variable/value2", "Brightness of color 2", context.brightness2 * 255.0, 255.0),
+        (
+            "/variable/direction",
+            "1 runs the program forwards, 0 backwards",
+            if context.run_forwards { 1.0 } else { 0.0 },
+            1.0,
+        ),
+        ("/variable/interval", "Program speed (40 is normal speed)", model.fps, 255.0),
+        (
+            "/variable/stopstart",
+            "1 stops the program and 0 runs it, no arguments pauses or resumes it",
+            if model.paused { 1.0 } else { 0.0 },
+            1.0,
+        ),
+    ];
+
+    for (address, description, value, max) in globals.iter() {
+        insert(&mut root, address, float_node(description, *value, 0.0, *max));
+    }
+
+    insert(&mut root, "/1/push1", trigger_node("Switches to the on program"));
+
+    insert(&mut root, "/tempo/tap", trigger_node("Sets the tempo from the time between taps"));
+    insert(&mut root, "/tempo/bpm", float_node("Beats per minute", context.clock.bpm, 20.0, 300.0));
+    insert(&mut root, "/tempo/beats_per_bar", float_node(
+        "Beats per bar",
+        context.clock.beats_per_bar as f32,
+        1.0,
+        16.0,
+    ));
+
+    for info in crate::programs::PROGRAMS {
+        insert(&mut root, &format!("/program/{}", info.name), trigger_node(info.description));
+
+        if !info.beat_lockable {
+            continue;
+        }
+
+        let ticks_per_beat = context.beat_locks.get(info.name).copied().unwrap_or(0.0);
+
+        insert(&mut root, &format!("/tempo/lock/{}", info.name), json!({
+            "TYPE": "f",
+            "VALUE": [ticks_per_beat],
+            "RANGE": [{ "MIN": 0.0 }],
+            "ACCESS": ACCESS_READ_WRITE,
+            "DESCRIPTION": "Frames the program runs per beat, 0 to run at the program speed",
+        }));
+    }
+
+    if let Some(exec) = &model.program_exec {
+        insert(&mut root, "/program", json!({
+            "DESCRIPTION": format!("Selects the program (currently {:?})", exec.info.name),
+        }));
+
+        for (param, value) in exec.params() {
+            let node = match (&param.kind, value) {
+                (ParamKind::Float { min, max, .. }, Some(ParamValue::Float(value))) => {
+                    float_node(param.description, value, *min, *max)
+                }
+                (ParamKind::Toggle { .. }, Some(ParamValue::Toggle(value))) => {
+                    float_node(param.description, if value { 1.0 } else { 0.0 }, 0.0, 1.0)
+                }
+                (ParamKind::Text { .. }, Some(ParamValue::Text(value))) => {
+                    text_node(param.description, &value, None)
+                }
+                (ParamKind::Trigger, _) => trigger_node(param.description),
+                // Programs that don't report the value (eg. inside the preprogram)
+                (kind, _) => {
+                    let mut node = json!({
+                        "TYPE": if let ParamKind::Text { .. } = kind { "s" } else { "f" },
+                        "ACCESS": ACCESS_WRITE,
+                        "DESCRIPTION": param.description,
+                    });
+
+                    if let ParamKind::Float { min, max, .. } = kind {
+                        node["RANGE"] = json!([{ "MIN": min, "MAX": max }]);
+                    }
+
+                    node
+                }
+            };
+
+            insert(&mut root, param.address, node);
+        }
+    }
+
+    let transition_kinds = [TransitionKind::Fade, TransitionKind::Wipe, TransitionKind::Dissolve]
+        .iter()
+        .map(TransitionKind::name)
+        .collect::<Vec<_>>();
+
+    insert(&mut root, "/transition/type", text_node(
+        "How the LEDs change over from one program to the next",
+        model.transition.kind.name(),
+        Some(&transition_kinds),
+    ));
+    insert(&mut root, "/transition/duration", json!({
+        "TYPE": "f",
+        "VALUE": [model.transition.duration],
+        "RANGE": [{ "MIN": 0.0 }],
+        "ACCESS": ACCESS_READ_WRITE,
+        "DESCRIPTION": "Seconds to transition between programs, 0 cuts straight to the new program",
+    }));
+
+    root
+}
+
+fn float_node(description: &str, value: f32, min: f32, max: f32) -> Value {
+    json!({
+        "TYPE": "f",
+        "VALUE": [value],
+        "RANGE": [{ "MIN": min, "MAX": max }],
+        "ACCESS": ACCESS_READ_WRITE,
+        "DESCRIPTION": description,
+    })
+}
+
+fn text_node(description: &str, value: &str, values: Option<&[&str]>) -> Value {
+    let mut node = json!({
+        "TYPE": "s",
+        "VALUE": [value],
+        "ACCESS": ACCESS_READ_WRITE,
+        "DESCRIPTION": description,
+    });
+
+    if let Some(values) = values {
+        node["RANGE"] = json!([{ "VALS": values }]);
+    }
+
+    node
+}
+
+/// An address that takes no arguments
+fn trigger_node(description: &str) -> Value {
+    json!({
+        "ACCESS": ACCESS_WRITE,
+        "DESCRIPTION": description,
+    })
+}
+
+/// Adds the attributes of the node at `address`, creating its parent nodes as needed
+fn insert(root: &mut Value, address: &str, attributes: Value) {
+    let mut node = root;
+    let mut full_path = String::new();
+
+    for part in address.trim_start_matches('/').split('/') {
+        full_path.push('/');
+        full_path.push_str(part);
+
+        let contents = node
+            .as_object_mut()
+            .expect("OSCQuery nodes are objects")
+            .entry("CONTENTS")
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        node = contents
+            .as_object_mut()
+            .expect("OSCQuery contents are objects")
+            .entry(part)
+            .or_insert_with(|| json!({ "FULL_PATH": full_path }));
+    }
+
+    if let (Some(node), Value::Object(attributes)) = (node.as_object_mut(), attributes) {
+        node.extend(attributes);
+    }
+}
+
+/// The node at `path` or `None` if there isn't one
+fn find<'a>(root: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('/')
+        .filter(|part| !part.is_empty())
+        .try_fold(root, |node, part| node.get("CONTENTS")?.get(part))
+}
+
+fn respond(stream: TcpStream, namespace: &Mutex<Value>, host_info: &Value) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Skip the headers
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut request = request_line.split_whitespace();
+
+    let target = match (request.next(), request.next()) {
+        (Some("GET"), Some(target)) => target,
+        _ => return write_response(&stream, "405 Method Not Allowed", None),
+    };
+
+    let (path, attribute) = match target.split_once('?') {
+        Some((path, attribute)) => (path, Some(attribute)),
+        None => (target, None),
+    };
+
+    if attribute == Some("HOST_INFO") {
+        return write_response(&stream, "200 OK", Some(host_info));
+    }
+
+    let namespace = namespace
+        .lock()
+        .map_err(|_| eyre!("OSCQuery namespace lock poisoned"))?;
+
+    match (find(&namespace, path), attribute) {
+        (None, _) => write_response(&stream, "404 Not Found", None),
+        (Some(node), None) => write_response(&stream, "200 OK", Some(node)),
+        (Some(node), Some(attribute)) => match node.get(attribute) {
+            Some(value) => {
+                write_response(&stream, "200 OK", Some(&json!({ attribute: value })))
+            }
+            None => write_response(&stream, "204 No Content", None),
+        },
+    }
+}
+
+fn write_response(mut stream: &TcpStream, status: &str, body: Option<&Value>) -> Result<()> {
+    let body = body.map(Value::to_string).unwrap_or_default();
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body,
+    )?;
+
+    Ok(())
+}
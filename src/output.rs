@@ -0,0 +1,102 @@
+use std::net::UdpSocket;
+
+use eyre::{Context, Result, eyre};
+
+use crate::{
+    color_correction::ColorCorrection,
+    config::Config,
+    outputs::{ArtNetOutput, OscOutput, Recorder, SacnOutput},
+};
+
+/// A frame sink. Each output is sent the rendered LEDs once per update.
+pub trait Output where Self: std::fmt::Debug {
+    fn send(&mut self, led_strips: &crate::LedStripVec) -> Result<()>;
+
+    /// The color correction the LEDs are encoded with, if the output drives LEDs
+    fn correction(&self) -> Option<&ColorCorrection> {
+        None
+    }
+
+    /// Output-specific settings, received as `/output/<index>/...`
+    fn receive_osc_packet<'a>(
+        &mut self,
+        addr: &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+    ) -> Result<()> {
+        Err(eyre!("Unsupported packet received. addr: {:?} args: {:?}", addr, args))
+    }
+}
+
+/// Creates every output listed in the config
+pub fn outputs_from_config(config: &Config) -> Result<Vec<Box<dyn Output>>> {
+    let mut outputs: Vec<Box<dyn Output>> = vec![];
+
+    let osc_routes = config.osc_routes()?;
+
+    if !osc_routes.is_empty() {
+        let led_controller_socket = UdpSocket::bind(("0.0.0.0", config.bind_port))
+            .wrap_err_with(|| format!(
+                "Unable to open port {} for LED controller connection",
+                config.bind_port,
+            ))?;
+
+        for route in osc_routes.iter() {
+            println!(
+                "Output #{}: Sending to LED Controller at: {}{}",
+                outputs.len(),
+                route.controller,
+                route.address,
+            );
+        }
+
+        outputs.push(Box::new(OscOutput::new(
+            led_controller_socket,
+            osc_routes,
+            config.osc_correction.clone(),
+        )));
+    }
+
+    for artnet in config.artnet.iter() {
+        let target = artnet.target_addr()?;
+
+        println!(
+            "Output #{}: Sending Art-Net to: {} (starting at universe {})",
+            outputs.len(),
+            target,
+            artnet.start_universe,
+        );
+
+        outputs.push(Box::new(ArtNetOutput::new(
+            target,
+            artnet.start_universe,
+            artnet.correction.clone(),
+        )?));
+    }
+
+    for sacn in config.sacn.iter() {
+        let target = sacn.target_addr()?;
+
+        println!(
+            "Output #{}: Sending sACN to: {} ({:?})",
+            outputs.len(),
+            target.map(|addr| addr.to_string()).unwrap_or_else(|| "multicast".to_string()),
+            sacn.universes(),
+        );
+
+        outputs.push(Box::new(SacnOutput::new(
+            target,
+            sacn.universes(),
+            sacn.priority,
+            &sacn.source_name,
+            sacn.correction.clone(),
+        )?));
+    }
+
+    if let Some(record_path) = &config.record {
+        println!("Output #{}: Recording frames to: {:?}", outputs.len(), record_path);
+
+        outputs.push(Box::new(Recorder::create(record_path)?));
+    }
+
+    Ok(outputs)
+}
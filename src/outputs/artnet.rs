@@ -0,0 +1,192 @@
+use std::net::{SocketAddr, UdpSocket};
+
+use eyre::{Context, Result, eyre};
+
+use crate::{
+    color_correction::{ColorCorrection, Pixel},
+    output::Output,
+};
+
+/// The standard Art-Net UDP port
+pub const ARTNET_PORT: u16 = 6454;
+/// Port addresses are 15 bits
+pub const MAX_ARTNET_UNIVERSE: u16 = 0x7fff;
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const OP_DMX: u16 = 0x5000;
+const PROTOCOL_VERSION: u16 = 14;
+const HEADER_LEN: usize = 18;
+
+/// Sends frames as ArtDmx packets, 170 RGB (or 128 RGBW) pixels per universe. All the LEDs are
+/// packed into consecutive universes starting from `start_universe`.
+#[derive(Debug)]
+pub struct ArtNetOutput {
+    socket: UdpSocket,
+    target: SocketAddr,
+    start_universe: u16,
+    sequence: u8,
+    correction: ColorCorrection,
+}
+
+impl ArtNetOutput {
+    pub fn new(
+        target: SocketAddr,
+        start_universe: u16,
+        correction: ColorCorrection,
+    ) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .wrap_err("Unable to open a UDP port for Art-Net output")?;
+
+        // Art-Net targets are often broadcast addresses (eg. 2.255.255.255)
+        socket.set_broadcast(true)?;
+
+        Ok(Self {
+            socket,
+            target,
+            start_universe,
+            sequence: 0,
+            correction,
+        })
+    }
+
+    fn dmx_packet(&self, universe: u16, pixels: &[Pixel]) -> Vec<u8> {
+        let mut data = pixels
+            .iter()
+            .flat_map(|pixel| pixel.as_slice())
+            .copied()
+            .collect::<Vec<_>>();
+
+        // The DMX data length must be even
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+
+        let mut packet = Vec::with_capacity(HEADER_LEN + data.len());
+
+        packet.extend_from_slice(ARTNET_ID);
+        packet.extend_from_slice(&OP_DMX.to_le_bytes());
+        packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        packet.push(self.sequence);
+        // Physical input port (informational only)
+        packet.push(0);
+        // 15 bit port address: SubUni (low byte) followed by Net (high 7 bits)
+        packet.extend_from_slice(&universe.to_le_bytes());
+        packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&data);
+
+        packet
+    }
+}
+
+impl Output for ArtNetOutput {
+    fn send(&mut self, led_strips: &crate::LedStripVec) -> Result<()> {
+        // A sequence of 0 disables re-ordering on the receiver so it is skipped
+        self.sequence = self.sequence.checked_add(1).unwrap_or(1);
+
+        let pixels = self.correction
+            .encode_frame(led_strips)
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let pixels_per_universe = super::DMX_UNIVERSE_SIZE / self.correction.channels_per_pixel();
+
+        let mut failed_universes = vec![];
+
+        for (universe_offset, universe_pixels) in pixels.chunks(pixels_per_universe).enumerate() {
+            let universe = self.start_universe as usize + universe_offset;
+
+            // The config is validated, but switching to RGBW needs more universes
+            if universe > MAX_ARTNET_UNIVERSE as usize {
+                return Err(eyre!(
+                    "Art-Net universe {} is out of range, only universes up to {} can be sent",
+                    universe,
+                    MAX_ARTNET_UNIVERSE,
+                ));
+            }
+
+            let universe = universe as u16;
+            let packet = self.dmx_packet(universe, universe_pixels);
+
+            if let Err(err) = self.socket.send_to(&packet, self.target) {
+                trace!("UDP Error: {:?}", err);
+                failed_universes.push(universe);
+            }
+        }
+
+        if !failed_universes.is_empty() {
+            return Err(eyre!(
+                "Failed to send Art-Net universe(s) {:?} to {}",
+                failed_universes,
+                self.target,
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn correction(&self) -> Option<&ColorCorrection> {
+        Some(&self.correction)
+    }
+
+    fn receive_osc_packet<'a>(
+        &mut self,
+        addr: &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+    ) -> Result<()> {
+        self.correction.receive_osc_packet(addr, args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use nannou::color::hsl;
+
+    use super::*;
+
+    #[test]
+    fn sends_artdmx_universes() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut output = ArtNetOutput::new(
+            receiver.local_addr().unwrap(),
+            5,
+            ColorCorrection::default(),
+        ).unwrap();
+
+        // One full universe of 170 red LEDs and a single blue LED on the next universe
+        let mut led_strip = vec![hsl(0.0, 1.0, 0.5); 170];
+        led_strip.push(hsl(2.0 / 3.0, 1.0, 0.5));
+
+        let led_strips = vec![led_strip];
+        output.send(&led_strips).unwrap();
+
+        let mut buffer = [0; 1024];
+
+        let len = receiver.recv(&mut buffer).unwrap();
+        let packet = &buffer[..len];
+
+        assert_eq!(&packet[..8], ARTNET_ID);
+        assert_eq!(&packet[8..10], &[0x00, 0x50]);
+        assert_eq!(&packet[10..12], &[0, 14]);
+        // Sequence
+        assert_eq!(packet[12], 1);
+        assert_eq!(&packet[14..16], &[5, 0]);
+        assert_eq!(&packet[16..18], &510u16.to_be_bytes());
+        assert_eq!(len, HEADER_LEN + 510);
+        assert_eq!(&packet[HEADER_LEN..HEADER_LEN + 6], &[255, 0, 0, 255, 0, 0]);
+        assert_eq!(&packet[len - 3..], &[255, 0, 0]);
+
+        let len = receiver.recv(&mut buffer).unwrap();
+        let packet = &buffer[..len];
+
+        assert_eq!(&packet[14..16], &[6, 0]);
+        // 3 channels padded to an even length
+        assert_eq!(&packet[16..18], &4u16.to_be_bytes());
+        assert_eq!(len, HEADER_LEN + 4);
+        assert_eq!(&packet[HEADER_LEN..], &[0, 0, 255, 0]);
+    }
+}
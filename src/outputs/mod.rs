@@ -0,0 +1,27 @@
+use nannou::color::Rgba;
+
+mod artnet;
+pub use artnet::{ArtNetOutput, ARTNET_PORT, MAX_ARTNET_UNIVERSE};
+
+mod osc;
+pub use osc::{OscOutput, OscRoute, LedRange};
+
+mod recorder;
+pub use recorder::Recorder;
+
+mod sacn;
+pub use sacn::{SacnOutput, SacnUniverses, SACN_PORT};
+
+/// Number of channels in a DMX universe
+pub const DMX_UNIVERSE_SIZE: usize = 512;
+
+/// Converts a LED's color to 8 bit red, green and blue values
+pub fn to_rgb8(color: &crate::LedColor) -> [u8; 3] {
+    let rgba = Into::<Rgba>::into(*color);
+
+    [
+        (rgba.red * 255.0) as u8,
+        (rgba.green * 255.0) as u8,
+        (rgba.blue * 255.0) as u8,
+    ]
+}
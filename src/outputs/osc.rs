@@ -0,0 +1,108 @@
+use std::net::{SocketAddr, UdpSocket};
+
+use eyre::{Result, eyre};
+use rosc::{OscColor, OscMessage, OscPacket, OscType};
+
+use crate::{
+    color_correction::{ColorCorrection, Pixel},
+    output::Output,
+};
+
+/// Sends frames to the LED controller(s) as OSC messages of colors over UDP
+#[derive(Debug)]
+pub struct OscOutput {
+    socket: UdpSocket,
+    routes: Vec<OscRoute>,
+    correction: ColorCorrection,
+}
+
+/// Sends a range of LEDs to an OSC address on a LED controller
+#[derive(Debug, Clone)]
+pub struct OscRoute {
+    pub controller: SocketAddr,
+    /// eg. "/led_strips/0"
+    pub address: String,
+    /// The LEDs to send or every LED of every strip if `None`
+    pub leds: Option<LedRange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LedRange {
+    pub strip: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl OscOutput {
+    pub fn new(socket: UdpSocket, routes: Vec<OscRoute>, correction: ColorCorrection) -> Self {
+        Self {
+            socket,
+            routes,
+            correction,
+        }
+    }
+}
+
+impl Output for OscOutput {
+    fn send(&mut self, led_strips: &crate::LedStripVec) -> Result<()> {
+        let pixels = self.correction.encode_frame(led_strips);
+
+        let mut failed_routes = vec![];
+
+        for route in self.routes.iter() {
+            let route_pixels: Box<dyn Iterator<Item = &Pixel>> = match &route.leds {
+                Some(range) => Box::new(pixels[range.strip][range.start..range.end].iter()),
+                None => Box::new(pixels.iter().flatten()),
+            };
+
+            let led_control_packet = OscPacket::Message(OscMessage {
+                addr: route.address.clone(),
+                args: route_pixels
+                    .map(|pixel| {
+                        let channels = pixel.as_slice();
+
+                        // The alpha channel carries the white channel of RGBW pixels
+                        OscType::Color(OscColor {
+                            red: channels[0],
+                            blue: channels[2],
+                            green: channels[1],
+                            alpha: pixel.white().unwrap_or(1.0 as u8),
+                        })
+                    })
+                    .collect(),
+            });
+
+            let packet_buf = rosc::encoder::encode(&led_control_packet).unwrap();
+
+            if let Err(err) = self.socket.send_to(&packet_buf, route.controller) {
+                trace!("UDP Error: {:?}", err);
+                failed_routes.push(format!("{}{}", route.controller, route.address));
+            } else {
+                trace!(
+                    "UDP packet sent to {}{}! ({} bytes)",
+                    route.controller,
+                    route.address,
+                    packet_buf.len(),
+                );
+            }
+        }
+
+        if !failed_routes.is_empty() {
+            return Err(eyre!("Failed to send UDP packet to LED controller(s) at {:?}", failed_routes));
+        }
+
+        Ok(())
+    }
+
+    fn correction(&self) -> Option<&ColorCorrection> {
+        Some(&self.correction)
+    }
+
+    fn receive_osc_packet<'a>(
+        &mut self,
+        addr: &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+    ) -> Result<()> {
+        self.correction.receive_osc_packet(addr, args)
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use eyre::{Context, Result};
+
+use crate::output::Output;
+
+/// Records frames to a file. Each frame is written as (all numbers little endian):
+///
+/// - milliseconds since the recording started (u64)
+/// - number of LED strips (u16)
+/// - for each strip: the number of LEDs (u16) followed by 3 bytes (red, green, blue) per LED
+#[derive(Debug)]
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started_at: Instant,
+}
+
+impl Recorder {
+    pub fn create(file_path: impl AsRef<Path>) -> Result<Self> {
+        let file_path = file_path.as_ref();
+
+        let file = File::create(file_path)
+            .wrap_err_with(|| format!("Unable to create recording: {:?}", file_path))?;
+
+        Ok(Self {
+            writer: BufWriter::new(file),
+            started_at: Instant::now(),
+        })
+    }
+}
+
+impl Output for Recorder {
+    fn send(&mut self, led_strips: &crate::LedStripVec) -> Result<()> {
+        let millis = self.started_at.elapsed().as_millis() as u64;
+
+        self.writer.write_all(&millis.to_le_bytes())?;
+        self.writer.write_all(&(led_strips.len() as u16).to_le_bytes())?;
+
+        for led_strip in led_strips.iter() {
+            self.writer.write_all(&(led_strip.len() as u16).to_le_bytes())?;
+
+            for led in led_strip.iter() {
+                self.writer.write_all(&super::to_rgb8(led))?;
+            }
+        }
+
+        self.writer.flush()
+            .wrap_err("Unable to write frame to recording")
+    }
+}
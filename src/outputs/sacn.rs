@@ -0,0 +1,269 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+};
+
+use eyre::{Context, Result, eyre};
+use nannou::rand;
+
+use crate::{
+    color_correction::{ColorCorrection, Pixel},
+    output::Output,
+};
+
+/// The standard sACN UDP port
+pub const SACN_PORT: u16 = 5568;
+
+const ACN_PACKET_IDENTIFIER: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_E131_DATA: u32 = 0x0000_0004;
+const VECTOR_E131_DATA_PACKET: u32 = 0x0000_0002;
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const SOURCE_NAME_LEN: usize = 64;
+/// Byte offsets of the framing and DMP layers within a data packet
+const FRAMING_LAYER_OFFSET: usize = 38;
+const DMP_LAYER_OFFSET: usize = 115;
+
+/// Sends frames as E1.31 (sACN) data packets, 170 RGB (or 128 RGBW) pixels per universe
+#[derive(Debug)]
+pub struct SacnOutput {
+    socket: UdpSocket,
+    /// Unicast address or `None` to multicast each universe to its 239.255.x.x group
+    target: Option<SocketAddr>,
+    universes: SacnUniverses,
+    priority: u8,
+    source_name: [u8; SOURCE_NAME_LEN],
+    cid: [u8; 16],
+    /// The next sequence number of each universe
+    sequence_numbers: BTreeMap<u16, u8>,
+    correction: ColorCorrection,
+}
+
+/// How the LEDs are mapped onto universes
+#[derive(Debug, Clone)]
+pub enum SacnUniverses {
+    /// Every LED is packed into consecutive universes starting from this one
+    Consecutive(u16),
+    /// Each strip starts on its own universe
+    PerStrip(Vec<u16>),
+}
+
+impl SacnOutput {
+    pub fn new(
+        target: Option<SocketAddr>,
+        universes: SacnUniverses,
+        priority: u8,
+        source_name: &str,
+        correction: ColorCorrection,
+    ) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .wrap_err("Unable to open a UDP port for sACN output")?;
+
+        if target.is_none() {
+            socket.set_multicast_ttl_v4(16)?;
+        }
+
+        // The source name is a null-terminated string which is truncated to fit
+        let mut source_name_buf = [0u8; SOURCE_NAME_LEN];
+        let source_name = source_name.as_bytes();
+        let name_len = source_name.len().min(SOURCE_NAME_LEN - 1);
+        source_name_buf[..name_len].copy_from_slice(&source_name[..name_len]);
+
+        Ok(Self {
+            socket,
+            target,
+            universes,
+            priority,
+            source_name: source_name_buf,
+            // A random ID for this source (component identifier)
+            cid: rand::random(),
+            sequence_numbers: BTreeMap::new(),
+            correction,
+        })
+    }
+
+    /// Packs the LEDs into DMX data for each universe
+    fn universe_data(&mut self, led_strips: &crate::LedStripVec) -> Vec<(u16, Vec<u8>)> {
+        let pixels_per_universe = super::DMX_UNIVERSE_SIZE / self.correction.channels_per_pixel();
+        let pixels = self.correction.encode_frame(led_strips);
+
+        let pack = |start_universe: u16, pixels: &[Pixel]| {
+            pixels
+                .chunks(pixels_per_universe)
+                .enumerate()
+                .map(|(offset, pixels)| (
+                    start_universe.wrapping_add(offset as u16),
+                    pixels.iter().flat_map(|pixel| pixel.as_slice()).copied().collect(),
+                ))
+                .collect::<Vec<_>>()
+        };
+
+        match &self.universes {
+            SacnUniverses::Consecutive(start_universe) => {
+                pack(*start_universe, &pixels.concat())
+            }
+            SacnUniverses::PerStrip(strip_universes) => {
+                pixels
+                    .iter()
+                    .zip(strip_universes.iter())
+                    .flat_map(|(strip_pixels, start_universe)| {
+                        pack(*start_universe, strip_pixels)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn data_packet(&self, universe: u16, sequence_number: u8, data: &[u8]) -> Vec<u8> {
+        // The DMX start code is sent as the first property value
+        let property_value_count = data.len() + 1;
+        let packet_len = DMP_LAYER_OFFSET + 10 + property_value_count;
+
+        let flags_and_length = |offset: usize| (0x7000 | (packet_len - offset) as u16).to_be_bytes();
+
+        let mut packet = Vec::with_capacity(packet_len);
+
+        // Root layer
+        packet.extend_from_slice(&0x0010u16.to_be_bytes());
+        packet.extend_from_slice(&0x0000u16.to_be_bytes());
+        packet.extend_from_slice(ACN_PACKET_IDENTIFIER);
+        packet.extend_from_slice(&flags_and_length(16));
+        packet.extend_from_slice(&VECTOR_ROOT_E131_DATA.to_be_bytes());
+        packet.extend_from_slice(&self.cid);
+
+        // Framing layer
+        packet.extend_from_slice(&flags_and_length(FRAMING_LAYER_OFFSET));
+        packet.extend_from_slice(&VECTOR_E131_DATA_PACKET.to_be_bytes());
+        packet.extend_from_slice(&self.source_name);
+        packet.push(self.priority);
+        // Synchronization address (unused)
+        packet.extend_from_slice(&0u16.to_be_bytes());
+        packet.push(sequence_number);
+        // Options
+        packet.push(0);
+        packet.extend_from_slice(&universe.to_be_bytes());
+
+        // DMP layer
+        packet.extend_from_slice(&flags_and_length(DMP_LAYER_OFFSET));
+        packet.push(VECTOR_DMP_SET_PROPERTY);
+        // Address type and data type
+        packet.push(0xa1);
+        // First property address
+        packet.extend_from_slice(&0u16.to_be_bytes());
+        // Address increment
+        packet.extend_from_slice(&1u16.to_be_bytes());
+        packet.extend_from_slice(&(property_value_count as u16).to_be_bytes());
+        // DMX start code
+        packet.push(0);
+        packet.extend_from_slice(data);
+
+        packet
+    }
+}
+
+impl Output for SacnOutput {
+    fn send(&mut self, led_strips: &crate::LedStripVec) -> Result<()> {
+        let universes = self.universe_data(led_strips);
+
+        let mut failed_universes = vec![];
+
+        for (universe, data) in universes.iter() {
+            let next_sequence_number = self.sequence_numbers.entry(*universe).or_insert(0);
+            let sequence_number = *next_sequence_number;
+            *next_sequence_number = sequence_number.wrapping_add(1);
+
+            let packet = self.data_packet(*universe, sequence_number, data);
+            let target = self.target.unwrap_or_else(|| multicast_addr(*universe));
+
+            if let Err(err) = self.socket.send_to(&packet, target) {
+                trace!("UDP Error: {:?}", err);
+                failed_universes.push(*universe);
+            }
+        }
+
+        if !failed_universes.is_empty() {
+            return Err(eyre!("Failed to send sACN universe(s) {:?}", failed_universes));
+        }
+
+        Ok(())
+    }
+
+    fn correction(&self) -> Option<&ColorCorrection> {
+        Some(&self.correction)
+    }
+
+    fn receive_osc_packet<'a>(
+        &mut self,
+        addr: &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+    ) -> Result<()> {
+        self.correction.receive_osc_packet(addr, args)
+    }
+}
+
+/// The multicast group of a universe (239.255.<high byte>.<low byte>)
+fn multicast_addr(universe: u16) -> SocketAddr {
+    let [high, low] = universe.to_be_bytes();
+
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, high, low)), SACN_PORT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use nannou::color::hsl;
+
+    use super::*;
+
+    #[test]
+    fn sends_e131_data_packets() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut output = SacnOutput::new(
+            Some(receiver.local_addr().unwrap()),
+            SacnUniverses::PerStrip(vec![3, 7]),
+            100,
+            "test",
+            ColorCorrection::default(),
+        ).unwrap();
+
+        // A red LED on the first strip and two green LEDs on the second
+        let led_strips = vec![
+            vec![hsl(0.0, 1.0, 0.5)],
+            vec![hsl(1.0 / 3.0, 1.0, 0.5); 2],
+        ];
+
+        let universes = [(3u16, vec![255, 0, 0]), (7, vec![0, 255, 0, 0, 255, 0])];
+        let mut buffer = [0; 1024];
+
+        for sequence_number in 0..2 {
+            output.send(&led_strips).unwrap();
+
+            for (universe, data) in universes.iter() {
+                let len = receiver.recv(&mut buffer).unwrap();
+                let packet = &buffer[..len];
+
+                assert_eq!(len, DMP_LAYER_OFFSET + 11 + data.len());
+
+                // Flags and length of the root, framing and DMP layers
+                for offset in [16, FRAMING_LAYER_OFFSET, DMP_LAYER_OFFSET].iter() {
+                    let flags_and_length = (0x7000 | (len - offset) as u16).to_be_bytes();
+                    assert_eq!(&packet[*offset..*offset + 2], &flags_and_length);
+                }
+
+                assert_eq!(&packet[4..16], ACN_PACKET_IDENTIFIER);
+                assert_eq!(&packet[44..48], b"test");
+                assert_eq!(packet[108], 100);
+                // Each universe counts its own sequence
+                assert_eq!(packet[111], sequence_number);
+                assert_eq!(&packet[113..115], &universe.to_be_bytes());
+                // Property value count (the start code and the data)
+                assert_eq!(&packet[123..125], &(data.len() as u16 + 1).to_be_bytes());
+                // DMX start code
+                assert_eq!(packet[125], 0);
+                assert_eq!(&packet[126..], &data[..]);
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use nannou::color::{IntoColor, Rgb};
+use serde::Deserialize;
+use eyre::{Result, eyre};
+
+use crate::color_correction::ColorCorrection;
+
+/// Power budget of the rig. Frames are scaled down so that the estimated current drawn from each
+/// supply stays within its rating.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PowerConfig {
+    /// Current drawn by a single color channel of a LED at full brightness
+    #[serde(default = "default_milliamps_per_channel")]
+    pub milliamps_per_channel: f32,
+    /// Current drawn by each LED while it is off
+    #[serde(default = "default_idle_milliamps_per_led")]
+    pub idle_milliamps_per_led: f32,
+    pub supplies: Vec<SupplyConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SupplyConfig {
+    /// Maximum current the supply can provide
+    pub amps: f32,
+    /// Indexes of the LED strips powered by this supply. Defaults to every strip.
+    pub strips: Option<Vec<usize>>,
+}
+
+fn default_milliamps_per_channel() -> f32 {
+    20.0
+}
+
+fn default_idle_milliamps_per_led() -> f32 {
+    1.0
+}
+
+impl PowerConfig {
+    pub fn validate(&self, strip_count: usize) -> Result<()> {
+        if self.supplies.is_empty() {
+            return Err(eyre!("At least one power supply is required"));
+        }
+
+        let valid_milliamps = self.milliamps_per_channel.is_finite()
+            && self.milliamps_per_channel > 0.0
+            && self.idle_milliamps_per_led.is_finite()
+            && self.idle_milliamps_per_led >= 0.0;
+
+        if !valid_milliamps {
+            return Err(eyre!("milliamps_per_channel and idle_milliamps_per_led must be positive"));
+        }
+
+        let mut powered_strips = vec![false; strip_count];
+
+        for (supply_index, supply) in self.supplies.iter().enumerate() {
+            if !supply.amps.is_finite() || supply.amps <= 0.0 {
+                return Err(eyre!("Supply #{} amps must be greater than 0", supply_index));
+            }
+
+            for strip_index in supply.strip_indexes(strip_count) {
+                match powered_strips.get_mut(strip_index) {
+                    Some(true) => {
+                        return Err(eyre!("LED strip #{} is on more than one supply", strip_index));
+                    }
+                    Some(powered) => *powered = true,
+                    None => {
+                        return Err(eyre!(
+                            "Supply #{}: LED strip #{} does not exist",
+                            supply_index,
+                            strip_index,
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SupplyConfig {
+    fn strip_indexes(&self, strip_count: usize) -> Vec<usize> {
+        match &self.strips {
+            Some(strips) => strips.clone(),
+            None => (0..strip_count).collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PowerLimiter {
+    milliamps_per_channel: f32,
+    idle_milliamps_per_led: f32,
+    supplies: Vec<Supply>,
+}
+
+#[derive(Debug)]
+pub struct Supply {
+    pub max_amps: f32,
+    pub strips: Vec<usize>,
+    /// Estimated current drawn by the last frame before it was scaled down
+    pub estimated_amps: f32,
+    /// The brightness the last frame was scaled to (1.0 if it was within budget)
+    pub scale: f32,
+}
+
+impl PowerLimiter {
+    pub fn new(config: &PowerConfig, strip_count: usize) -> Self {
+        Self {
+            milliamps_per_channel: config.milliamps_per_channel,
+            idle_milliamps_per_led: config.idle_milliamps_per_led,
+            supplies: config.supplies
+                .iter()
+                .map(|supply| Supply {
+                    max_amps: supply.amps,
+                    strips: supply.strip_indexes(strip_count),
+                    estimated_amps: 0.0,
+                    scale: 1.0,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn supplies(&self) -> &[Supply] {
+        &self.supplies
+    }
+
+    /// Estimates the current drawn by each supply from the channels the outputs send (after
+    /// their color correction, including any white channel). With several outputs the one that
+    /// draws the most is used. Returns a scaled down copy of the frame if any supply is over budget
+    /// or `None` if the frame can be sent as is.
+    pub fn limit(
+        &mut self,
+        led_strips: &crate::LedStripVec,
+        corrections: &[&ColorCorrection],
+    ) -> Option<crate::LedStripVec> {
+        let default_correction = ColorCorrection::default();
+
+        let corrections = if corrections.is_empty() {
+            vec![&default_correction]
+        } else {
+            corrections.to_vec()
+        };
+
+        let mut limited_strips: Option<crate::LedStripVec> = None;
+
+        for (supply_index, supply) in self.supplies.iter_mut().enumerate() {
+            let leds = || {
+                supply.strips
+                    .iter()
+                    .flat_map(|strip_index| led_strips[*strip_index].iter())
+            };
+
+            let idle_milliamps = leds().count() as f32 * self.idle_milliamps_per_led;
+            let max_milliamps = supply.max_amps * 1000.0;
+
+            let mut estimated_milliamps = idle_milliamps;
+            let mut scale = 1.0f32;
+
+            for correction in corrections.iter() {
+                let channel_total = leds()
+                    .map(|led| correction.channel_levels(led).iter().sum::<f32>())
+                    .sum::<f32>();
+
+                let active_milliamps = channel_total * self.milliamps_per_channel;
+                estimated_milliamps = estimated_milliamps.max(idle_milliamps + active_milliamps);
+
+                if idle_milliamps + active_milliamps > max_milliamps {
+                    // The corrected channels scale with the color raised to the gamma
+                    let budget = ((max_milliamps - idle_milliamps) / active_milliamps).max(0.0);
+                    scale = scale.min(budget.powf(1.0 / correction.gamma));
+                }
+            }
+
+            supply.estimated_amps = estimated_milliamps / 1000.0;
+
+            debug!("Supply #{}: {:.2}A estimated", supply_index, supply.estimated_amps);
+
+            if scale < 1.0 && supply.scale >= 1.0 {
+                warn!(
+                    "Supply #{} over budget ({:.2}A of {:.2}A). Limiting brightness.",
+                    supply_index,
+                    supply.estimated_amps,
+                    supply.max_amps,
+                );
+            } else if scale >= 1.0 && supply.scale < 1.0 {
+                info!("Supply #{} back within budget", supply_index);
+            }
+
+            supply.scale = scale;
+
+            if scale < 1.0 {
+                let limited_strips = limited_strips.get_or_insert_with(|| led_strips.clone());
+
+                for strip_index in supply.strips.iter() {
+                    for led in limited_strips[*strip_index].iter_mut() {
+                        let rgb = Into::<Rgb>::into(*led);
+
+                        *led = Rgb::new(rgb.red * scale, rgb.green * scale, rgb.blue * scale)
+                            .into_hsl();
+                    }
+                }
+            }
+        }
+
+        limited_strips
+    }
+}
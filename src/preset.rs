@@ -0,0 +1,228 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use nannou::color::{Mix, hsl};
+use serde::{Deserialize, Serialize};
+use eyre::{Context, Result, eyre};
+
+use crate::{
+    config::ColorConfig,
+    program::{ParamValue, ProgramContext, ProgramExecutor},
+    transition::{Transition, TransitionConfig, TransitionKind},
+};
+
+/// A saved look: the global settings along with the program and its parameters
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    /// Name of the program (eg. "pulse")
+    pub program: String,
+    /// Program speed
+    pub fps: f32,
+    pub run_forwards: bool,
+    pub global_brightness: f32,
+    pub brightness1: f32,
+    pub brightness2: f32,
+    pub color1: ColorConfig,
+    pub color2: ColorConfig,
+    /// The program's parameters by name (see `--list-programs`)
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl Preset {
+    /// Captures the current settings and the program's parameters
+    pub fn from_model(model: &crate::Model) -> Result<Self> {
+        let exec = model.program_exec
+            .as_ref()
+            .ok_or_else(|| eyre!("No program is running"))?;
+
+        let context = &model.context;
+
+        let color_config = |color: &crate::LedColor| ColorConfig {
+            hue: color.hue.to_positive_degrees(),
+            saturation: color.saturation,
+        };
+
+        Ok(Self {
+            program: exec.info.name.to_string(),
+            fps: model.fps,
+            run_forwards: context.run_forwards,
+            global_brightness: context.global_brightness_multiplier,
+            brightness1: context.brightness1,
+            brightness2: context.brightness2,
+            color1: color_config(&context.color),
+            color2: color_config(&context.color2),
+            params: exec.params()
+                .filter_map(|(param, value)| Some((param.name.to_string(), value?)))
+                .collect(),
+        })
+    }
+
+    pub fn load(file_path: impl AsRef<Path>) -> Result<Self> {
+        let file_path = file_path.as_ref();
+
+        let preset: Self = toml::from_str(&fs::read_to_string(file_path)
+            .wrap_err_with(|| format!("Unable to read preset: {:?}", file_path))?
+        )
+            .wrap_err_with(|| format!("Invalid preset: {:?}", file_path))?;
+
+        preset.validate()
+            .wrap_err_with(|| format!("Invalid preset: {:?}", file_path))?;
+
+        Ok(preset)
+    }
+
+    pub fn save(&self, file_path: impl AsRef<Path>) -> Result<()> {
+        let file_path = file_path.as_ref();
+
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir)
+                .wrap_err_with(|| format!("Unable to create the presets directory: {:?}", dir))?;
+        }
+
+        fs::write(file_path, toml::to_string(self)?)
+            .wrap_err_with(|| format!("Unable to write preset: {:?}", file_path))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.fps.is_finite() || self.fps < 0.0 {
+            return Err(eyre!("fps must be a positive number, got: {}", self.fps));
+        }
+
+        let fractions = [
+            ("global_brightness", self.global_brightness),
+            ("brightness1", self.brightness1),
+            ("brightness2", self.brightness2),
+            ("color1.saturation", self.color1.saturation),
+            ("color2.saturation", self.color2.saturation),
+        ];
+
+        for (field, value) in fractions.iter() {
+            if !(0.0..=1.0).contains(value) {
+                return Err(eyre!("{} must be between 0 and 1, got: {}", field, value));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn color1(&self) -> crate::LedColor {
+        hsl(
+            self.color1.hue / 360.0,
+            self.color1.saturation,
+            self.brightness1 * self.global_brightness,
+        )
+    }
+
+    fn color2(&self) -> crate::LedColor {
+        hsl(
+            self.color2.hue / 360.0,
+            self.color2.saturation,
+            self.brightness2 * self.global_brightness,
+        )
+    }
+
+    /// Sets the context's colors and brightnesses part of the way (0 to 1) from `from` to the
+    /// preset
+    fn apply(&self, from: &Preset, amount: f32, context: &mut ProgramContext) {
+        let lerp = |from: f32, to: f32| from + (to - from) * amount;
+
+        context.global_brightness_multiplier = lerp(from.global_brightness, self.global_brightness);
+        context.brightness1 = lerp(from.brightness1, self.brightness1);
+        context.brightness2 = lerp(from.brightness2, self.brightness2);
+        context.color = from.color1().mix(&self.color1(), amount);
+        context.color2 = from.color2().mix(&self.color2(), amount);
+    }
+
+    /// Switches to the preset's program and settings, fading to them over `seconds` or cutting
+    /// straight to them if 0
+    pub fn recall(self, model: &mut crate::Model, seconds: f32) -> Result<()> {
+        let fade = TransitionConfig { kind: TransitionKind::Fade, duration: seconds };
+        fade.validate()?;
+
+        let from = Preset::from_model(model)?;
+
+        let mut exec = ProgramExecutor::from_program_name(&self.program, &model.context)?;
+
+        for (name, value) in self.params.iter() {
+            if let Err(err) = exec.set_param(name, Some(value)) {
+                println!("{:?}", err.wrap_err(format!("Unable to restore the {} parameter", name)));
+            }
+        }
+
+        model.fps = self.fps;
+        model.context.run_forwards = self.run_forwards;
+
+        model.active_transition = model.program_exec
+            .replace(exec)
+            .and_then(|outgoing| Transition::new(fade, outgoing, &model.led_strips));
+
+        if seconds == 0.0 {
+            self.apply(&from, 1.0, &mut model.context);
+            model.preset_fade = None;
+        } else {
+            model.preset_fade = Some(PresetFade {
+                from,
+                to: self,
+                elapsed: Duration::ZERO,
+                duration: Duration::from_secs_f32(seconds),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Fades the colors and brightnesses from their values when a preset was recalled to the preset's,
+/// in real time regardless of the program speed
+#[derive(Debug)]
+pub struct PresetFade {
+    from: Preset,
+    to: Preset,
+    elapsed: Duration,
+    duration: Duration,
+}
+
+impl PresetFade {
+    /// Moves the settings closer to the preset by the time since the last update. Returns true once
+    /// the fade is finished.
+    pub fn update(&mut self, context: &mut ProgramContext, since_last: Duration) -> bool {
+        self.elapsed += since_last;
+
+        let amount = (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0);
+        self.to.apply(&self.from, amount, context);
+
+        self.elapsed >= self.duration
+    }
+}
+
+/// The file a preset number is saved to
+fn preset_path(presets_dir: &Path, number: &str) -> Result<PathBuf> {
+    let number = number
+        .parse::<u32>()
+        .map_err(|_| eyre!("Invalid preset number: {:?}", number))?;
+
+    Ok(presets_dir.join(format!("{}.toml", number)))
+}
+
+/// Saves the current settings, program and program parameters as a preset
+pub fn save(model: &crate::Model, number: &str) -> Result<()> {
+    let file_path = preset_path(&model.presets_dir, number)?;
+
+    Preset::from_model(model)?.save(&file_path)?;
+
+    println!("Saved preset {} to {:?}", number, file_path);
+    Ok(())
+}
+
+/// Restores a saved preset, fading to it over `seconds` or cutting straight to it if 0
+pub fn recall(model: &mut crate::Model, number: &str, seconds: f32) -> Result<()> {
+    Preset::load(preset_path(&model.presets_dir, number)?)?
+        .recall(model, seconds)
+        .wrap_err_with(|| format!("Unable to recall preset {}", number))
+}
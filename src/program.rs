@@ -1,3 +1,10 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use nannou::color::{IntoColor, Rgb};
+use serde::{Deserialize, Serialize};
+
+use crate::tempo::BeatClock;
+
 use eyre::{
     eyre,
     // Error,
@@ -5,52 +12,294 @@ use eyre::{
 };
 
 pub trait Program where Self: std::fmt::Debug {
-    fn update(&mut self, model: &mut crate::Model, frame_index: usize);
+    /// Draws the next frame into `led_strips`. The frame holds whatever the program drew last.
+    fn update(
+        &mut self,
+        context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        frame_index: usize,
+    );
     fn receive_osc_packet<'a>(
         &mut self,
         addr: &'a[&'a str],
         args: &'a[nannou_osc::Type],
         frame_index: usize,
     ) -> Result<()>;
+    /// The current value of one of the parameters in the program's `ProgramInfo`, in the same units
+    /// it is set in over OSC. `None` for triggers.
+    fn param_value(&self, name: &str) -> Option<ParamValue>;
+}
+
+/// Describes a program and its parameters so that they can be listed without running it
+#[derive(Debug)]
+pub struct ProgramInfo {
+    /// The name used to select the program (eg. `/program/<name>`)
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Whether the program is drawn from the frame index, so that it can be locked to the beat
+    pub beat_lockable: bool,
+    pub new: fn(&ProgramContext) -> Result<Box<dyn Program>>,
+    pub params: &'static [ParamInfo],
+}
+
+#[derive(Debug)]
+pub struct ParamInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// OSC address the parameter is set at (eg. "/variable/pulse_period")
+    pub address: &'static str,
+    pub kind: ParamKind,
+}
+
+#[derive(Debug)]
+pub enum ParamKind {
+    /// Sent as a single float
+    Float { min: f32, max: f32, default: f32 },
+    /// Sent as a float, 0 for off and 1 for on
+    Toggle { default: bool },
+    /// Sent as a single string
+    Text { default: &'static str },
+    /// An action rather than a value (eg. restarting the program), sent without any arguments
+    Trigger,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Float(f32),
+    Toggle(bool),
+    Text(String),
+}
+
+impl ParamValue {
+    pub fn to_osc(&self) -> nannou_osc::Type {
+        match self {
+            ParamValue::Float(value) => nannou_osc::Type::Float(*value),
+            ParamValue::Toggle(value) => nannou_osc::Type::Float(if *value { 1.0 } else { 0.0 }),
+            ParamValue::Text(value) => nannou_osc::Type::String(value.clone()),
+        }
+    }
+}
+
+impl fmt::Display for ParamKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamKind::Float { min, max, default } => {
+                write!(f, "{} to {} (default {})", min, max, default)
+            }
+            ParamKind::Toggle { default } => write!(f, "0 or 1 (default {})", *default as u8),
+            ParamKind::Text { default } => write!(f, "string (default {:?})", default),
+            ParamKind::Trigger => write!(f, "no arguments"),
+        }
+    }
+}
+
+/// Lists every program and its parameters
+pub fn print_programs() {
+    for info in crate::programs::PROGRAMS {
+        println!("{}: {}", info.name, info.description);
+
+        for param in info.params {
+            println!("    {} {} [{}]", param.address, param.kind, param.name);
+            println!("        {}", param.description);
+        }
+
+        println!();
+    }
 }
 
+impl ParamInfo {
+    /// The OSC address split into its parts, as received by `Program::receive_osc_packet`
+    pub fn addr(&self) -> Vec<&'static str> {
+        self.address.trim_start_matches('/').split('/').collect()
+    }
+
+    pub fn default_value(&self) -> Option<ParamValue> {
+        match self.kind {
+            ParamKind::Float { default, .. } => Some(ParamValue::Float(default)),
+            ParamKind::Toggle { default } => Some(ParamValue::Toggle(default)),
+            ParamKind::Text { default } => Some(ParamValue::Text(default.to_string())),
+            ParamKind::Trigger => None,
+        }
+    }
+
+    /// Checks the value against the parameter's type and range and converts it to OSC arguments.
+    /// Triggers take no value.
+    pub fn osc_args(&self, value: Option<&ParamValue>) -> Result<Vec<nannou_osc::Type>> {
+        use nannou_osc::Type;
+
+        match (&self.kind, value) {
+            (ParamKind::Float { min, max, .. }, Some(ParamValue::Float(value))) => {
+                if !(*min..=*max).contains(value) {
+                    return Err(eyre!(
+                        "{} must be between {} and {}, got: {}",
+                        self.name,
+                        min,
+                        max,
+                        value,
+                    ));
+                }
+                Ok(vec![Type::Float(*value)])
+            }
+            (ParamKind::Toggle { .. }, Some(value @ ParamValue::Toggle(_)))
+            | (ParamKind::Text { .. }, Some(value @ ParamValue::Text(_))) => Ok(vec![value.to_osc()]),
+            (ParamKind::Trigger, None) => Ok(vec![]),
+            _ => Err(eyre!("Invalid value for {} ({:?}): {:?}", self.name, self.kind, value)),
+        }
+    }
+}
+
+/// The settings shared by every program. Programs can read them but only draw into their own frame.
+#[derive(Debug, Clone)]
+pub struct ProgramContext {
+    pub global_brightness_multiplier: f32,
+    pub brightness1: f32,
+    pub brightness2: f32,
+    pub color: crate::LedColor,
+    pub color2: crate::LedColor,
+    pub run_forwards: bool,
+    /// Number of LEDs on each strip
+    pub strip_lens: Vec<usize>,
+    /// WAV file played back by the vumeter program
+    pub audio_file: Option<PathBuf>,
+    /// Playlist played by the preprogram program
+    pub playlist_file: Option<PathBuf>,
+    /// The musical beat (see `BeatClock::beat_phase` and `BeatClock::bar_phase`)
+    pub clock: BeatClock,
+    /// Frames per beat of the programs that run in time with the beat, by program name
+    pub beat_locks: BTreeMap<String, f32>,
+}
+
+pub fn total_led_count(led_strips: &crate::LedStripVec) -> usize {
+    led_strips
+        .iter()
+        .fold(0, |sum, led_strip| sum + led_strip.len())
+}
+
+/// Every LED of every strip, numbered continuously from the first LED of the first strip
+pub fn all_leds_mut(
+    led_strips: &mut crate::LedStripVec,
+) -> impl Iterator<Item = (usize, &mut crate::LedColor)> {
+    led_strips
+        .iter_mut()
+        .flat_map(|led_strip| led_strip.iter_mut())
+        .enumerate()
+}
+
+/// Blends two colors in RGB so that fades don't sweep through the hues in between
+pub fn crossfade(from: crate::LedColor, to: crate::LedColor, amount: f32) -> crate::LedColor {
+    let from = Into::<Rgb>::into(from);
+    let to = Into::<Rgb>::into(to);
+
+    Rgb::new(
+        from.red + (to.red - from.red) * amount,
+        from.green + (to.green - from.green) * amount,
+        from.blue + (to.blue - from.blue) * amount,
+    ).into_hsl()
+}
+
+#[derive(Debug)]
 pub struct ProgramExecutor {
+    pub info: &'static ProgramInfo,
     pub program: Box<dyn Program>,
     pub frame_index: usize,
+    /// Set while the program runs in time with the beat
+    beat_lock: Option<BeatLock>,
 }
 
-impl ProgramExecutor {
-    pub fn new(program: impl Program + 'static) -> Self {
-        Self {
-            program: Box::new(program),
-            frame_index: 0,
-        }
-    }
+/// Where the beat clock and the program were when the program was locked to the beat
+#[derive(Debug, Clone, Copy)]
+struct BeatLock {
+    ticks_per_beat: f32,
+    run_forwards: bool,
+    start_beats: f64,
+    start_frame: usize,
+    /// Frames run since the lock started
+    ticks: usize,
+}
 
+impl ProgramExecutor {
     pub fn from_program_name(
         program_name: &str,
-        model: &crate::Model
+        context: &ProgramContext,
     ) -> Result<Self> {
-        match program_name {
-            "blink" => Ok(Self::new(crate::programs::Blink::new(model)?)),
-            "on" => Ok(Self::new(crate::programs::On::new(model)?)),
-            // "pulse" => Ok(Box::new(crate::pulse::Pulse)),
-            "scanner" => Ok(Self::new(crate::programs::Scanner::default())),
-            // "fireworks" => Ok(Self::Fireworks(Fireworks)),
-            "theaterchase" => Ok(Self::new(crate::programs::TheaterChase::default())),
-            // "vumeter" => Ok(Self::VUMeter(VUMeter)),
-            // "preprogram" => Ok(Self::PreProgram(PreProgram)),
-            _ => Err(eyre!("Invalid program name: {}", program_name)),
-        }
+        let info = crate::programs::PROGRAMS
+            .iter()
+            .find(|info| info.name == program_name)
+            .ok_or_else(|| eyre!("Invalid program name: {}", program_name))?;
+
+        Ok(Self {
+            info,
+            program: (info.new)(context)?,
+            frame_index: 0,
+            beat_lock: None,
+        })
+    }
+
+    /// Each of the program's parameters with its current value
+    pub fn params(&self) -> impl Iterator<Item = (&'static ParamInfo, Option<ParamValue>)> + '_ {
+        self.info.params
+            .iter()
+            .map(move |param| (param, self.program.param_value(param.name)))
     }
 
-    pub fn update(&mut self, model: &mut crate::Model) {
-        self.program.update(model, self.frame_index);
+    /// Sets a parameter by name, sending it to the program at the parameter's OSC address
+    pub fn set_param(&mut self, name: &str, value: Option<&ParamValue>) -> Result<()> {
+        let param = self.info.params
+            .iter()
+            .find(|param| param.name == name)
+            .ok_or_else(|| eyre!("Invalid parameter for {}: {:?}", self.info.name, name))?;
+
+        let args = param.osc_args(value)?;
+
+        self.program.receive_osc_packet(&param.addr(), &args, self.frame_index)
+    }
+
+    pub fn update(&mut self, context: &ProgramContext, led_strips: &mut crate::LedStripVec) {
+        match context.beat_locks.get(self.info.name) {
+            Some(ticks_per_beat) => self.follow_beat(context, *ticks_per_beat),
+            None => self.beat_lock = None,
+        }
+
+        self.program.update(context, led_strips, self.frame_index);
 
-        self.frame_index = if model.run_forwards {
+        if self.beat_lock.is_some() {
+            return;
+        }
+
+        self.frame_index = if context.run_forwards {
             self.frame_index.wrapping_add(1)
         } else {
             self.frame_index.wrapping_sub(1)
         }
     }
+
+    /// Sets the frame index from the beat clock so that the program runs `ticks_per_beat` frames
+    /// per beat, whatever the program speed
+    fn follow_beat(&mut self, context: &ProgramContext, ticks_per_beat: f32) {
+        let lock = self.beat_lock
+            .filter(|lock| {
+                lock.ticks_per_beat == ticks_per_beat && lock.run_forwards == context.run_forwards
+            })
+            .unwrap_or(BeatLock {
+                ticks_per_beat,
+                run_forwards: context.run_forwards,
+                start_beats: context.clock.beats,
+                start_frame: self.frame_index,
+                ticks: 0,
+            });
+
+        // Never go back in time (eg. when a tap pulls the clock back to line up with the beat)
+        let ticks = ((context.clock.beats - lock.start_beats) * ticks_per_beat as f64)
+            .max(0.0) as usize;
+        let ticks = ticks.max(lock.ticks);
+
+        self.frame_index = if lock.run_forwards {
+            lock.start_frame.wrapping_add(ticks)
+        } else {
+            lock.start_frame.wrapping_sub(ticks)
+        };
+
+        self.beat_lock = Some(BeatLock { ticks, ..lock });
+    }
 }
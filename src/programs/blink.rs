@@ -4,23 +4,56 @@ use eyre::{
     // Error,
     Result,
 };
-use crate::program::Program;
+use crate::program::{
+    self,
+    ParamInfo,
+    ParamKind,
+    ParamValue,
+    Program,
+    ProgramContext,
+    ProgramInfo,
+};
+
+const DEFAULT_PALETTE: &str = "bhw1_14";
+
+pub const PROGRAM: ProgramInfo = ProgramInfo {
+    name: "blink",
+    description: "Each LED blinks through the palette at random intervals",
+    beat_lockable: true,
+    new: |context| Ok(Box::new(Blink::new(context)?)),
+    params: &[
+        ParamInfo {
+            name: "random_time",
+            description: "Longest time between the blinks of a LED in milliseconds",
+            address: "/variable/blinkrandomtime",
+            kind: ParamKind::Float { min: 0.0, max: 60000.0, default: 10000.0 },
+        },
+        ParamInfo {
+            name: "palette",
+            description: "Name of the palette the LEDs blink through (see the palettes directory)",
+            address: "/palette",
+            kind: ParamKind::Text { default: DEFAULT_PALETTE },
+        },
+    ],
+};
 
 #[derive(Debug)]
 pub struct Blink {
     max_ticks_until_blink: usize,
     led_next_blink: Vec<usize>,
+    palette_name: String,
     gradient: Gradient<Hsl>,
 }
 
 impl Blink {
-    pub fn new(model: &crate::Model) -> Result<Self> {
+    pub fn new(context: &ProgramContext) -> Result<Self> {
         let max_ticks_until_blink = 400;
 
         let mut blink = Self {
             max_ticks_until_blink,
-            led_next_blink: vec![0; model.total_led_count()],
-            gradient: crate::svg_palette::to_gradient("bhw1_14").unwrap(),
+            led_next_blink: vec![0; context.strip_lens.iter().sum()],
+            palette_name: DEFAULT_PALETTE.to_string(),
+            gradient: crate::svg_palette::to_gradient(DEFAULT_PALETTE).unwrap(),
         };
 
         blink.led_next_blink.fill_with(|| {
@@ -29,18 +62,31 @@ impl Blink {
 
         Ok(blink)
     }
+
+    fn set_palette(&mut self, palette_name: &str) -> Result<()> {
+        self.gradient = crate::svg_palette::to_gradient(palette_name)?;
+        self.palette_name = palette_name.to_string();
+        Ok(())
+    }
 }
 
 impl Program for Blink {
-    fn update(&mut self, model: &mut crate::Model, frame_index: usize) {
+    fn update(
+        &mut self,
+        _context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        frame_index: usize,
+    ) {
         let gradient_size: usize = 255;
 
-        for (led_index, led_color) in model.all_leds_mut() {
+        for (led_index, led_color) in program::all_leds_mut(led_strips) {
             if frame_index >= self.led_next_blink[led_index] {
-                let led_gradient_index = frame_index - self.led_next_blink[led_index];
+                // The frame index can skip ahead when the program is locked to the beat
+                let led_gradient_index = (frame_index - self.led_next_blink[led_index])
+                    .min(gradient_size);
                 *led_color = self.gradient.get(led_gradient_index as f32);
 
-                if led_gradient_index == gradient_size {
+                if led_gradient_index >= gradient_size {
                     let next_blink_offset = if self.max_ticks_until_blink == 0 {
                         0
                     } else {
@@ -70,7 +116,10 @@ impl Program for Blink {
                     .ok_or_else(|| eyre!("Invalid tail_length"))?;
             },
             (["palette", palette_name], _) => {
-                self.gradient = crate::svg_palette::to_gradient(palette_name)?;
+                self.set_palette(palette_name)?;
+            }
+            (["palette"], [String(palette_name)]) => {
+                self.set_palette(palette_name)?;
             }
             _ => {
                 return Err(eyre!("Unsupported packet received. addr: {:?} args: {:?}", addr, args))
@@ -78,4 +127,13 @@ impl Program for Blink {
         };
         Ok(())
     }
+
+    fn param_value(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            // ticks are calculated at 40 fps
+            "random_time" => Some(ParamValue::Float(self.max_ticks_until_blink as f32 * 1000.0 / 40.0)),
+            "palette" => Some(ParamValue::Text(self.palette_name.clone())),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,277 @@
+use nannou::{color::{Gradient, Hsl}, prelude::ToPrimitive, rand};
+use eyre::{
+    eyre,
+    // Error,
+    Result,
+};
+use crate::program::{
+    self,
+    ParamInfo,
+    ParamKind,
+    ParamValue,
+    Program,
+    ProgramContext,
+    ProgramInfo,
+};
+
+const DEFAULT_PALETTE: &str = "bhw2_22";
+
+pub const PROGRAM: ProgramInfo = ProgramInfo {
+    name: "fireworks",
+    description: "Shells launched along the strips that burst into fading sparks",
+    beat_lockable: false,
+    new: |context| Ok(Box::new(Fireworks::new(context)?)),
+    params: &[
+        ParamInfo {
+            name: "rate",
+            description: "Average number of shells launched per second",
+            address: "/variable/fireworks_rate",
+            kind: ParamKind::Float { min: 0.0, max: 40.0, default: 1.0 },
+        },
+        ParamInfo {
+            name: "burst",
+            description: "Number of sparks in each burst",
+            address: "/variable/fireworks_burst",
+            kind: ParamKind::Float { min: 0.0, max: 200.0, default: 12.0 },
+        },
+        ParamInfo {
+            name: "decay",
+            description: "Seconds for a spark to fade out",
+            address: "/variable/fireworks_decay",
+            kind: ParamKind::Float { min: 0.025, max: 60.0, default: 1.0 },
+        },
+        ParamInfo {
+            name: "palette",
+            description: "Name of the palette the sparks are colored from (see the palettes directory)",
+            address: "/palette",
+            kind: ParamKind::Text { default: DEFAULT_PALETTE },
+        },
+    ],
+};
+
+/// Fraction of a spark's speed kept from one frame to the next
+const SPARK_DRAG: f32 = 0.9;
+/// Shells slow down by this many LEDs per frame until they burst at the top of their flight
+const SHELL_GRAVITY: f32 = 0.02;
+
+/// Launches shells from random points along the strips which rise, slow down and then burst into
+/// sparks that spread out in both directions and fade away.
+#[derive(Debug)]
+pub struct Fireworks {
+    /// Average number of shells launched per second
+    launch_rate: f32,
+    /// Number of sparks in each burst
+    burst_size: usize,
+    /// Fraction of a spark's brightness kept from one frame to the next
+    decay: f32,
+    palette_name: String,
+    gradient: Gradient<Hsl>,
+    particles: Vec<Particle>,
+}
+
+#[derive(Debug)]
+struct Particle {
+    kind: ParticleKind,
+    strip_index: usize,
+    /// Position along the strip in LEDs
+    position: f32,
+    /// Velocity in LEDs per frame
+    velocity: f32,
+    color: crate::LedColor,
+    brightness: f32,
+}
+
+#[derive(Debug, PartialEq)]
+enum ParticleKind {
+    Shell,
+    Spark,
+}
+
+impl Fireworks {
+    pub fn new(_context: &ProgramContext) -> Result<Self> {
+        let fireworks = Self {
+            launch_rate: 1.0,
+            burst_size: 12,
+            decay: decay_per_frame(1.0),
+            palette_name: DEFAULT_PALETTE.to_string(),
+            gradient: crate::svg_palette::to_gradient(DEFAULT_PALETTE)?,
+            particles: vec![],
+        };
+
+        Ok(fireworks)
+    }
+
+    fn set_palette(&mut self, palette_name: &str) -> Result<()> {
+        self.gradient = crate::svg_palette::to_gradient(palette_name)?;
+        self.palette_name = palette_name.to_string();
+        Ok(())
+    }
+
+    fn launch(&mut self, context: &ProgramContext) {
+        let strip_index = rand::random::<usize>() % context.strip_lens.len();
+        let strip_len = context.strip_lens[strip_index] as f32;
+
+        // Shells rise in either direction along the strip
+        let direction = if rand::random::<bool>() { 1.0 } else { -1.0 };
+        let speed = 0.5 + rand::random::<f32>() * 0.5;
+
+        self.particles.push(Particle {
+            kind: ParticleKind::Shell,
+            strip_index,
+            position: rand::random::<f32>() * strip_len,
+            velocity: direction * speed,
+            color: context.color,
+            brightness: 0.5,
+        });
+    }
+
+    fn burst(&mut self, shell: &Particle) {
+        let color = self.gradient.get(rand::random::<f32>() * 100.0);
+
+        let sparks = (0..self.burst_size).map(|spark_index| {
+            // Alternate the sparks between both directions
+            let direction = if spark_index % 2 == 0 { 1.0 } else { -1.0 };
+
+            Particle {
+                kind: ParticleKind::Spark,
+                strip_index: shell.strip_index,
+                position: shell.position,
+                velocity: direction * rand::random::<f32>() * 1.5,
+                color,
+                brightness: 1.0,
+            }
+        });
+
+        self.particles.extend(sparks);
+    }
+}
+
+impl Program for Fireworks {
+    fn update(
+        &mut self,
+        context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        _frame_index: usize,
+    ) {
+        // Launches are calculated at 40 fps (but can be scaled by the program speed)
+        if rand::random::<f32>() < self.launch_rate / 40.0 {
+            self.launch(context);
+        }
+
+        for particle in self.particles.iter_mut() {
+            particle.position += particle.velocity;
+
+            match particle.kind {
+                ParticleKind::Shell => {
+                    let speed = (particle.velocity.abs() - SHELL_GRAVITY).max(0.0);
+                    particle.velocity = particle.velocity.signum() * speed;
+                }
+                ParticleKind::Spark => {
+                    particle.velocity *= SPARK_DRAG;
+                    particle.brightness *= self.decay;
+                }
+            }
+        }
+
+        // Burst the shells that have reached the top of their flight and drop the faded sparks
+        let particles = std::mem::take(&mut self.particles);
+        let mut bursting_shells = vec![];
+
+        for particle in particles {
+            if particle.kind == ParticleKind::Shell && particle.velocity == 0.0 {
+                bursting_shells.push(particle);
+            } else if particle.brightness >= 0.01 {
+                self.particles.push(particle);
+            }
+        }
+
+        for shell in bursting_shells.iter() {
+            self.burst(shell);
+        }
+
+        // Draw the particles over a black background, keeping the brightest at each LED
+        for (_, led_color) in program::all_leds_mut(led_strips) {
+            led_color.lightness = 0.0;
+        }
+
+        for particle in self.particles.iter() {
+            let led_strip = &mut led_strips[particle.strip_index];
+            let led_index = particle.position.round();
+
+            if led_index < 0.0 || led_index >= led_strip.len() as f32 {
+                continue;
+            }
+
+            let led_color = &mut led_strip[led_index as usize];
+            let lightness = particle.color.lightness * particle.brightness;
+
+            if lightness > led_color.lightness {
+                *led_color = particle.color;
+                led_color.lightness = lightness;
+            }
+        }
+    }
+
+    fn receive_osc_packet<'a>(
+        &mut self,
+        addr:  &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+        _frame_index: usize,
+    ) -> Result<()> {
+        use nannou_osc::Type::*;
+        match (addr, args) {
+            (["variable", "fireworks_rate"], [
+                Float(launches_per_second),
+            ]) => {
+                if !(0.0..=40.0).contains(launches_per_second) {
+                    return Err(eyre!("Invalid fireworks_rate"));
+                }
+                self.launch_rate = *launches_per_second;
+            }
+            (["variable", "fireworks_burst"], [
+                Float(burst_size),
+            ]) => {
+                self.burst_size = burst_size
+                    .to_usize()
+                    .filter(|burst_size| *burst_size <= 200)
+                    .ok_or_else(|| eyre!("Invalid fireworks_burst"))?;
+            }
+            (["variable", "fireworks_decay"], [
+                Float(seconds),
+            ]) => {
+                // Longer decays round to keeping all of the brightness each frame
+                if !(1.0 / 40.0..=60.0).contains(seconds) {
+                    return Err(eyre!("Invalid fireworks_decay"));
+                }
+                self.decay = decay_per_frame(*seconds);
+            }
+            (["palette", palette_name], _) => {
+                self.set_palette(palette_name)?;
+            }
+            (["palette"], [String(palette_name)]) => {
+                self.set_palette(palette_name)?;
+            }
+            _ => {
+                return Err(eyre!("Unsupported packet received. addr: {:?} args: {:?}", addr, args))
+            }
+        };
+        Ok(())
+    }
+
+    fn param_value(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "rate" => Some(ParamValue::Float(self.launch_rate)),
+            "burst" => Some(ParamValue::Float(self.burst_size as f32)),
+            // The inverse of decay_per_frame
+            "decay" => Some(ParamValue::Float(0.01f32.ln() / self.decay.ln() / 40.0)),
+            "palette" => Some(ParamValue::Text(self.palette_name.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// The fraction of a spark's brightness to keep each frame (at 40 fps) for it to fade to 1% of its
+/// brightness in `seconds`
+fn decay_per_frame(seconds: f32) -> f32 {
+    0.01f32.powf(1.0 / (seconds * 40.0))
+}
@@ -1,14 +1,22 @@
-mod blink;
-pub use blink::Blink;
+use crate::program::ProgramInfo;
 
+mod blink;
+mod fireworks;
+mod on;
+mod preprogram;
 mod pulse;
-// pub use pulse::Pulse;
-
 mod scanner;
-pub use scanner::Scanner;
-
 mod theater_chase;
-pub use theater_chase::TheaterChase;
+mod vumeter;
 
-mod on;
-pub use on::On;
+/// Every program that can be selected by name
+pub const PROGRAMS: &[ProgramInfo] = &[
+    blink::PROGRAM,
+    fireworks::PROGRAM,
+    on::PROGRAM,
+    preprogram::PROGRAM,
+    pulse::PROGRAM,
+    scanner::PROGRAM,
+    theater_chase::PROGRAM,
+    vumeter::PROGRAM,
+];
@@ -4,7 +4,42 @@ use eyre::{
     Result,
 };
 use nannou::prelude::ToPrimitive;
-use crate::program::Program;
+use crate::program::{
+    self,
+    ParamInfo,
+    ParamKind,
+    ParamValue,
+    Program,
+    ProgramContext,
+    ProgramInfo,
+};
+
+pub const PROGRAM: ProgramInfo = ProgramInfo {
+    name: "on",
+    description: "Turns all the LEDs on for a period and then fades to black",
+    beat_lockable: false,
+    new: |context| Ok(Box::new(On::new(context)?)),
+    params: &[
+        ParamInfo {
+            name: "hold",
+            description: "Seconds to stay on before fading",
+            address: "/1/fader1",
+            kind: ParamKind::Float { min: 0.0, max: 60.0, default: 0.125 },
+        },
+        ParamInfo {
+            name: "fade",
+            description: "Seconds to fade to black",
+            address: "/1/fader2",
+            kind: ParamKind::Float { min: 0.0, max: 60.0, default: 0.5 },
+        },
+        ParamInfo {
+            name: "restart",
+            description: "Turns the LEDs back on",
+            address: "/1/push2",
+            kind: ParamKind::Trigger,
+        },
+    ],
+};
 
 /// Turns all the LEDs on for a period and then fades to black
 #[derive(Debug)]
@@ -15,7 +50,7 @@ pub struct On {
 }
 
 impl On {
-    pub fn new(_model: &crate::Model) -> Result<Self> {
+    pub fn new(_context: &ProgramContext) -> Result<Self> {
         let on = Self {
             first_frame: 0,
             ticks_until_start_of_fade: 5,
@@ -27,20 +62,25 @@ impl On {
 }
 
 impl Program for On {
-    fn update(&mut self, model: &mut crate::Model, frame_index: usize) {
+    fn update(
+        &mut self,
+        context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        frame_index: usize,
+    ) {
         let frame_index = frame_index - self.first_frame;
 
         if frame_index == 0 {
             // Turn all the LEDs on
-            let color = model.color.clone();
+            let color = context.color.clone();
 
-            for (_, led_color) in model.all_leds_mut() {
+            for (_, led_color) in program::all_leds_mut(led_strips) {
                 *led_color = color.clone();
             }
         }
         if frame_index >= self.ticks_until_start_of_fade {
             // Fade all the LEDs out
-            for (_, led_color) in model.all_leds_mut() {
+            for (_, led_color) in program::all_leds_mut(led_strips) {
                 led_color.lightness = (
                     led_color.lightness - 1.0 / (self.fade_ticks as f32)
                 ).max(0.0);
@@ -81,4 +121,13 @@ impl Program for On {
 
         Ok(())
     }
+
+    fn param_value(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            // ticks are calculated at 40 fps
+            "hold" => Some(ParamValue::Float(self.ticks_until_start_of_fade as f32 / 40.0)),
+            "fade" => Some(ParamValue::Float(self.fade_ticks as f32 / 40.0)),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,269 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Deserialize;
+use eyre::{
+    eyre,
+    Context,
+    Result,
+};
+use crate::{
+    layer::Layer,
+    program::{
+        ParamInfo,
+        ParamKind,
+        ParamValue,
+        Program,
+        ProgramContext,
+        ProgramExecutor,
+        ProgramInfo,
+        crossfade,
+    },
+};
+
+pub const PROGRAM: ProgramInfo = ProgramInfo {
+    name: "preprogram",
+    description: "Plays the playlist file on a loop, crossfading between its programs",
+    beat_lockable: false,
+    new: |context| Ok(Box::new(PreProgram::new(context)?)),
+    params: &[
+        ParamInfo {
+            name: "next",
+            description: "Skips to the next step of the playlist",
+            address: "/preprogram/next",
+            kind: ParamKind::Trigger,
+        },
+    ],
+};
+
+/// A show of programs played one after another
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Playlist {
+    /// Seconds (at 40 fps) to crossfade from one step to the next
+    #[serde(default)]
+    pub crossfade: f32,
+    pub steps: Vec<PlaylistStep>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PlaylistStep {
+    /// Name of the program to run (eg. "pulse")
+    pub program: String,
+    /// Seconds (at 40 fps) to run the program for
+    pub duration: f32,
+    /// OSC messages sent to the program when the step starts, keyed by address
+    /// (eg. `"/variable/pulse_period" = 4.0`)
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamArgs>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ParamArgs {
+    One(ParamArg),
+    Many(Vec<ParamArg>),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ParamArg {
+    Float(f32),
+    String(String),
+}
+
+impl Playlist {
+    pub fn load(file_path: impl AsRef<Path>) -> Result<Self> {
+        let file_path = file_path.as_ref();
+
+        let playlist: Self = toml::from_str(&fs::read_to_string(file_path)
+            .wrap_err_with(|| format!("Unable to read playlist: {:?}", file_path))?
+        )
+            .wrap_err_with(|| format!("Invalid playlist: {:?}", file_path))?;
+
+        playlist.validate()
+            .wrap_err_with(|| format!("Invalid playlist: {:?}", file_path))?;
+
+        Ok(playlist)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.steps.is_empty() {
+            return Err(eyre!("At least one step is required"));
+        }
+
+        if !self.crossfade.is_finite() || self.crossfade < 0.0 {
+            return Err(eyre!("crossfade must be a positive number, got: {}", self.crossfade));
+        }
+
+        for (step_index, step) in self.steps.iter().enumerate() {
+            if step.program == "preprogram" {
+                return Err(eyre!("Invalid step #{}: playlists cannot be nested", step_index));
+            }
+
+            if !step.duration.is_finite() || step.duration <= 0.0 {
+                return Err(eyre!(
+                    "Invalid step #{}: duration must be greater than 0, got: {}",
+                    step_index,
+                    step.duration,
+                ));
+            }
+
+            if let Some(address) = step.params.keys().find(|address| !address.starts_with('/')) {
+                return Err(eyre!(
+                    "Invalid step #{}: OSC address must start with a '/', got: {:?}",
+                    step_index,
+                    address,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl PlaylistStep {
+    fn duration_ticks(&self) -> usize {
+        // ticks are calculated at 40 fps (but can be scaled by the program speed)
+        ((self.duration * 40.0) as usize).max(1)
+    }
+
+    /// Starts the step's program drawing into its own (initially blank) frame
+    fn start(&self, context: &ProgramContext) -> Result<Layer> {
+        let mut exec = ProgramExecutor::from_program_name(&self.program, context)?;
+
+        for (address, args) in self.params.iter() {
+            let addr = address.trim_start_matches('/').split('/').collect::<Vec<_>>();
+
+            let args = match args {
+                ParamArgs::One(arg) => vec![arg.to_osc()],
+                ParamArgs::Many(args) => args.iter().map(ParamArg::to_osc).collect(),
+            };
+
+            exec.program.receive_osc_packet(&addr, &args, exec.frame_index)?;
+        }
+
+        Ok(Layer::blank(exec, context))
+    }
+}
+
+/// Plays the steps of a playlist on a loop, crossfading from each program to the next
+#[derive(Debug)]
+pub struct PreProgram {
+    playlist: Playlist,
+    step_index: usize,
+    /// Frames played of the current step
+    step_ticks: usize,
+    current: Layer,
+    /// The program being faded out during a crossfade
+    previous: Option<Layer>,
+}
+
+impl ParamArg {
+    fn to_osc(&self) -> nannou_osc::Type {
+        match self {
+            ParamArg::Float(value) => nannou_osc::Type::Float(*value),
+            ParamArg::String(value) => nannou_osc::Type::String(value.clone()),
+        }
+    }
+}
+
+impl PreProgram {
+    pub fn new(context: &ProgramContext) -> Result<Self> {
+        let file_path = context.playlist_file
+            .as_ref()
+            .ok_or_else(|| eyre!("The preprogram requires a playlist file (see `playlist` in config.toml)"))?;
+
+        let playlist = Playlist::load(file_path)?;
+
+        // Start every step once up front so that mistakes are caught before the show
+        for (step_index, step) in playlist.steps.iter().enumerate() {
+            step.start(context)
+                .wrap_err_with(|| format!("Invalid playlist step #{}", step_index))?;
+        }
+
+        let current = playlist.steps[0].start(context)?;
+
+        Ok(Self {
+            playlist,
+            step_index: 0,
+            step_ticks: 0,
+            current,
+            previous: None,
+        })
+    }
+
+    fn next_step(&mut self, context: &ProgramContext) {
+        self.step_index = (self.step_index + 1) % self.playlist.steps.len();
+        self.step_ticks = 0;
+
+        match self.playlist.steps[self.step_index].start(context) {
+            Ok(layer) => {
+                self.previous = Some(std::mem::replace(&mut self.current, layer));
+            }
+            Err(err) => println!("{:?}", err),
+        }
+    }
+}
+
+impl Program for PreProgram {
+    fn update(
+        &mut self,
+        context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        _frame_index: usize,
+    ) {
+        if self.step_ticks >= self.playlist.steps[self.step_index].duration_ticks() {
+            self.next_step(context);
+        }
+
+        self.current.update(context);
+
+        let crossfade_ticks = (self.playlist.crossfade * 40.0) as usize;
+
+        match &mut self.previous {
+            Some(previous) if self.step_ticks < crossfade_ticks => {
+                previous.update(context);
+
+                let amount = self.step_ticks as f32 / crossfade_ticks as f32;
+
+                let frames = previous.led_strips.iter().zip(self.current.led_strips.iter());
+
+                for (led_strip, (from, to)) in led_strips.iter_mut().zip(frames) {
+                    for (led_color, (from, to)) in led_strip.iter_mut().zip(from.iter().zip(to.iter())) {
+                        *led_color = crossfade(*from, *to, amount);
+                    }
+                }
+            }
+            _ => {
+                self.previous = None;
+                led_strips.clone_from(&self.current.led_strips);
+            }
+        }
+
+        self.step_ticks += 1;
+    }
+
+    fn receive_osc_packet<'a>(
+        &mut self,
+        addr:  &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+        _frame_index: usize,
+    ) -> Result<()> {
+        match (addr, args) {
+            (["preprogram", "next"], _) => {
+                // Advance on the next update
+                self.step_ticks = usize::MAX;
+            }
+            // Pass everything else through to the current program
+            _ => {
+                self.current.exec.program.receive_osc_packet(addr, args, self.current.exec.frame_index)?;
+            }
+        };
+        Ok(())
+    }
+
+    fn param_value(&self, _name: &str) -> Option<ParamValue> {
+        None
+    }
+}
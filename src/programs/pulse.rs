@@ -1,10 +1,192 @@
-// use crate::program::Program;
+use std::f32::consts::{E, PI};
 
-// #[derive(Debug)]
-// pub struct Pulse;
+use nannou::prelude::ToPrimitive;
+use eyre::{
+    eyre,
+    // Error,
+    Result,
+};
+use crate::program::{
+    self,
+    ParamInfo,
+    ParamKind,
+    ParamValue,
+    Program,
+    ProgramContext,
+    ProgramInfo,
+};
 
-// impl Program for Pulse {
-//     fn update(&mut self, _model: &mut crate::Model) {
+pub const PROGRAM: ProgramInfo = ProgramInfo {
+    name: "pulse",
+    description: "Breathes all the LEDs in and out",
+    beat_lockable: true,
+    new: |_context| Ok(Box::new(Pulse::default())),
+    params: &[
+        ParamInfo {
+            name: "waveform",
+            description: "1: sine, 2: triangle, 3: exponential",
+            address: "/variable/pulse_waveform",
+            kind: ParamKind::Float { min: 1.0, max: 3.0, default: 1.0 },
+        },
+        ParamInfo {
+            name: "period",
+            description: "Seconds per breath",
+            address: "/variable/pulse_period",
+            kind: ParamKind::Float { min: 0.025, max: 600.0, default: 2.0 },
+        },
+        ParamInfo {
+            name: "min",
+            description: "Brightness at the bottom of each breath",
+            address: "/variable/pulse_min",
+            kind: ParamKind::Float { min: 0.0, max: 255.0, default: 0.0 },
+        },
+        ParamInfo {
+            name: "max",
+            description: "Brightness at the top of each breath",
+            address: "/variable/pulse_max",
+            kind: ParamKind::Float { min: 0.0, max: 255.0, default: 255.0 },
+        },
+        ParamInfo {
+            name: "color2",
+            description: "Fades from color2 at the bottom of each breath to color1 at the top",
+            address: "/variable/pulse_color2",
+            kind: ParamKind::Toggle { default: false },
+        },
+    ],
+};
 
-//     }
-// }
+/// Breathes all the LEDs in and out between a minimum and maximum brightness. When `use_color2`
+/// is set the color also fades from color2 (at the minimum) to color1 (at the maximum).
+#[derive(Debug)]
+pub struct Pulse {
+    waveform: PulseWaveform,
+    period_ticks: usize,
+    min_brightness: f32,
+    max_brightness: f32,
+    use_color2: bool,
+}
+
+#[derive(Debug)]
+pub enum PulseWaveform {
+    Sine,
+    Triangle,
+    /// A slow rise and a sharp peak which looks more like breathing than a sine wave
+    Exponential,
+}
+
+impl PulseWaveform {
+    /// The level of the waveform (0 to 1) at a phase (0 to 1) of the period
+    fn level(&self, phase: f32) -> f32 {
+        match self {
+            PulseWaveform::Sine => 0.5 - 0.5 * (2.0 * PI * phase).cos(),
+            PulseWaveform::Triangle => 1.0 - (2.0 * phase - 1.0).abs(),
+            PulseWaveform::Exponential => {
+                let breath = (-(2.0 * PI * phase).cos()).exp();
+                (breath - 1.0 / E) / (E - 1.0 / E)
+            }
+        }
+    }
+}
+
+impl Default for Pulse {
+    fn default() -> Self {
+        Self {
+            waveform: PulseWaveform::Sine,
+            // 2 seconds at 40 fps
+            period_ticks: 80,
+            min_brightness: 0.0,
+            max_brightness: 1.0,
+            use_color2: false,
+        }
+    }
+}
+
+impl Program for Pulse {
+    fn update(
+        &mut self,
+        context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        frame_index: usize,
+    ) {
+        // frame_index counts down when running backwards so the pulse plays in reverse
+        let phase = (frame_index % self.period_ticks) as f32 / self.period_ticks as f32;
+        let level = self.waveform.level(phase);
+        let brightness = self.min_brightness + (self.max_brightness - self.min_brightness) * level;
+
+        let mut color = if self.use_color2 {
+            program::crossfade(context.color2, context.color, level)
+        } else {
+            context.color
+        };
+        color.lightness *= brightness;
+
+        for (_, led_color) in program::all_leds_mut(led_strips) {
+            *led_color = color;
+        }
+    }
+
+    fn receive_osc_packet<'a>(
+        &mut self,
+        addr:  &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+        _frame_index: usize,
+    ) -> Result<()> {
+        use nannou_osc::Type::*;
+        match (addr, args) {
+            (["variable", "pulse_waveform"], [
+                Float(waveform_id),
+            ]) => {
+                self.waveform = match waveform_id.to_u8() {
+                    Some(1u8) => PulseWaveform::Sine,
+                    Some(2u8) => PulseWaveform::Triangle,
+                    Some(3u8) => PulseWaveform::Exponential,
+                    _ => return Err(eyre!("Invalid pulse waveform: {:?}", waveform_id)),
+                };
+            }
+            (["variable", "pulse_period"], [
+                Float(seconds),
+            ]) => {
+                // ticks are calculated at 40 fps (but can be scaled by the program speed)
+                self.period_ticks = (seconds * 40.0)
+                    .to_usize()
+                    .filter(|ticks| *ticks > 0)
+                    .ok_or_else(|| eyre!("Invalid pulse_period"))?;
+            }
+            (["variable", "pulse_min"], [
+                Float(brightness),
+            ]) => {
+                self.min_brightness = (brightness / 255.0).clamp(0.0, 1.0);
+            }
+            (["variable", "pulse_max"], [
+                Float(brightness),
+            ]) => {
+                self.max_brightness = (brightness / 255.0).clamp(0.0, 1.0);
+            }
+            (["variable", "pulse_color2"], [
+                Float(enabled),
+            ]) => {
+                self.use_color2 = *enabled != 0.0;
+            }
+            _ => {
+                return Err(eyre!("Unsupported packet received. addr: {:?} args: {:?}", addr, args))
+            }
+        };
+        Ok(())
+    }
+
+    fn param_value(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "waveform" => Some(ParamValue::Float(match self.waveform {
+                PulseWaveform::Sine => 1.0,
+                PulseWaveform::Triangle => 2.0,
+                PulseWaveform::Exponential => 3.0,
+            })),
+            // ticks are calculated at 40 fps
+            "period" => Some(ParamValue::Float(self.period_ticks as f32 / 40.0)),
+            "min" => Some(ParamValue::Float(self.min_brightness * 255.0)),
+            "max" => Some(ParamValue::Float(self.max_brightness * 255.0)),
+            "color2" => Some(ParamValue::Toggle(self.use_color2)),
+            _ => None,
+        }
+    }
+}
@@ -4,7 +4,30 @@ use eyre::{
     // Error,
     Result,
 };
-use crate::program::Program;
+use crate::program::{
+    self,
+    ParamInfo,
+    ParamKind,
+    ParamValue,
+    Program,
+    ProgramContext,
+    ProgramInfo,
+};
+
+pub const PROGRAM: ProgramInfo = ProgramInfo {
+    name: "scanner",
+    description: "A LED bouncing back and forth along each strip with a fading tail",
+    beat_lockable: false,
+    new: |_context| Ok(Box::new(Scanner::default())),
+    params: &[
+        ParamInfo {
+            name: "tail_length",
+            description: "Number of frames for the tail to fade out",
+            address: "/variable/tail_length",
+            kind: ParamKind::Float { min: 1.0, max: 1000.0, default: 30.0 },
+        },
+    ],
+};
 
 #[derive(Debug)]
 pub struct Scanner {
@@ -29,22 +52,28 @@ impl Default for Scanner {
 }
 
 impl Program for Scanner {
-    fn update(&mut self, model: &mut crate::Model, frame_index: usize) {
+    fn update(
+        &mut self,
+        context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        frame_index: usize,
+    ) {
         match self.mode {
             ScannerMode::ContinuousStrip => {
+                let led_count = program::total_led_count(led_strips);
                 self.update_leds(
-                    model.color.clone(),
-                    model.total_led_count(),
-                    model.all_leds_mut(),
+                    context.color.clone(),
+                    led_count,
+                    program::all_leds_mut(led_strips),
                     frame_index,
                 );
             }
             ScannerMode::ParallelStrips => {
-                for led_strip in model.led_strips.iter_mut() {
+                for led_strip in led_strips.iter_mut() {
                     let strip_len = led_strip.len();
                     let leds = led_strip.iter_mut().enumerate();
                     self.update_leds(
-                        model.color.clone(),
+                        context.color.clone(),
                         strip_len,
                         leds,
                         frame_index,
@@ -73,6 +102,13 @@ impl Program for Scanner {
         };
         Ok(())
     }
+
+    fn param_value(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "tail_length" => Some(ParamValue::Float(self.tail_length)),
+            _ => None,
+        }
+    }
 }
 
 impl Scanner {
@@ -4,7 +4,42 @@ use eyre::{
     // Error,
     Result,
 };
-use crate::program::Program;
+use crate::program::{
+    self,
+    ParamInfo,
+    ParamKind,
+    ParamValue,
+    Program,
+    ProgramContext,
+    ProgramInfo,
+};
+
+pub const PROGRAM: ProgramInfo = ProgramInfo {
+    name: "theaterchase",
+    description: "Groups of LEDs chasing each other along the strips",
+    beat_lockable: true,
+    new: |_context| Ok(Box::new(TheaterChase::default())),
+    params: &[
+        ParamInfo {
+            name: "mode",
+            description: "1: color1, 2: rainbow",
+            address: "/variable/chase_mode",
+            kind: ParamKind::Float { min: 1.0, max: 2.0, default: 1.0 },
+        },
+        ParamInfo {
+            name: "pixel_width",
+            description: "Number of LEDs lit in each group",
+            address: "/variable/pixel_width",
+            kind: ParamKind::Float { min: 0.0, max: 1000.0, default: 10.0 },
+        },
+        ParamInfo {
+            name: "pixel_distance",
+            description: "Number of LEDs from the start of one group to the next",
+            address: "/variable/pixel_distance",
+            kind: ParamKind::Float { min: 1.0, max: 1000.0, default: 25.0 },
+        },
+    ],
+};
 
 #[derive(Debug)]
 pub struct TheaterChase {
@@ -31,12 +66,17 @@ impl Default for TheaterChase {
 }
 
 impl Program for TheaterChase {
-    fn update(&mut self, model: &mut crate::Model, frame_index: usize) {
-        let program_index = frame_index % (model.total_led_count() * 2);
-        let color1 = model.color.clone();
-        let color2 = model.color2.clone();
+    fn update(
+        &mut self,
+        context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        frame_index: usize,
+    ) {
+        let program_index = frame_index % (program::total_led_count(led_strips) * 2);
+        let color1 = context.color.clone();
+        let color2 = context.color2.clone();
 
-        for (led_index, led_color) in model.all_leds_mut() {
+        for (led_index, led_color) in program::all_leds_mut(led_strips) {
             let distance_to_leading_pixel = (led_index + program_index) % self.pixel_distance;
 
             *led_color = if distance_to_leading_pixel < self.tail_length {
@@ -101,4 +141,16 @@ impl Program for TheaterChase {
         Ok(())
     }
 
+    fn param_value(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "mode" => Some(ParamValue::Float(match self.mode {
+                TheaterChaseMode::Regular => 1.0,
+                TheaterChaseMode::Rainbow => 2.0,
+            })),
+            "pixel_width" => Some(ParamValue::Float(self.tail_length as f32)),
+            "pixel_distance" => Some(ParamValue::Float(self.pixel_distance as f32)),
+            _ => None,
+        }
+    }
+
 }
@@ -0,0 +1,264 @@
+use std::{path::PathBuf, sync::mpsc::{Receiver, TryRecvError}};
+
+use nannou::{color::{Gradient, Hsl, hsl}, prelude::ToPrimitive};
+use eyre::{
+    eyre,
+    // Error,
+    Result,
+};
+use crate::{
+    audio::WavLevels,
+    program::{ParamInfo, ParamKind, ParamValue, Program, ProgramContext, ProgramInfo},
+};
+
+const DEFAULT_PALETTE: &str = "greeny";
+
+pub const PROGRAM: ProgramInfo = ProgramInfo {
+    name: "vumeter",
+    description: "Audio level meters with peak indicators",
+    beat_lockable: false,
+    new: |context| Ok(Box::new(VUMeter::new(context)?)),
+    params: &[
+        ParamInfo {
+            name: "level",
+            description: "Audio level, replacing the audio file",
+            address: "/audio/level",
+            kind: ParamKind::Float { min: 0.0, max: 255.0, default: 0.0 },
+        },
+        ParamInfo {
+            name: "file",
+            description: "WAV file to play back as the audio level",
+            address: "/audio/file",
+            kind: ParamKind::Text { default: "" },
+        },
+        ParamInfo {
+            name: "mirror",
+            description: "Grows the meters outwards from the center of each strip",
+            address: "/variable/vumeter_mirror",
+            kind: ParamKind::Toggle { default: false },
+        },
+        ParamInfo {
+            name: "hold",
+            description: "Seconds the peak indicators hold the loudest level for",
+            address: "/variable/vumeter_hold",
+            kind: ParamKind::Float { min: 0.0, max: 60.0, default: 1.0 },
+        },
+        ParamInfo {
+            name: "palette",
+            description: "Name of the palette the meters are colored from (see the palettes directory)",
+            address: "/palette",
+            kind: ParamKind::Text { default: DEFAULT_PALETTE },
+        },
+    ],
+};
+
+/// Fraction of the bar kept from one frame to the next as the level drops
+const RELEASE: f32 = 0.9;
+/// How far the peak falls per frame once its hold time is up
+const PEAK_FALL: f32 = 0.01;
+
+/// Draws the audio level as a bar on each strip with a peak indicator that holds at the loudest
+/// recent level. The level is read from a WAV file or received over OSC (`/audio/level`).
+#[derive(Debug)]
+pub struct VUMeter {
+    /// Plays back the audio file or `None` to use the levels received over OSC
+    audio: Option<WavLevels>,
+    audio_file: String,
+    /// An audio file that is loading, along with its path. Reported as the file so that it is
+    /// saved in presets before it has loaded.
+    loading_audio: Option<(String, Receiver<Result<WavLevels>>)>,
+    /// The latest level received over OSC (0 to 1)
+    osc_level: f32,
+    /// The level drawn by the bar
+    level: f32,
+    peak: f32,
+    peak_hold_ticks: usize,
+    ticks_until_peak_falls: usize,
+    /// Grows the bars outwards from the center of each strip
+    mirrored: bool,
+    palette_name: String,
+    gradient: Gradient<Hsl>,
+}
+
+impl VUMeter {
+    pub fn new(context: &ProgramContext) -> Result<Self> {
+        // Loaded in the background so that switching to the program doesn't hold up the LEDs
+        let loading_audio = context.audio_file
+            .as_ref()
+            .map(|path| {
+                let loading = WavLevels::open_in_background(path.clone());
+                (path.to_string_lossy().to_string(), loading)
+            });
+
+        let vumeter = Self {
+            audio: None,
+            audio_file: String::new(),
+            loading_audio,
+            osc_level: 0.0,
+            level: 0.0,
+            peak: 0.0,
+            peak_hold_ticks: 40,
+            ticks_until_peak_falls: 0,
+            mirrored: false,
+            palette_name: DEFAULT_PALETTE.to_string(),
+            gradient: crate::svg_palette::to_gradient(DEFAULT_PALETTE)?,
+        };
+
+        Ok(vumeter)
+    }
+
+    /// Switches to the audio file once it has loaded
+    fn receive_loaded_audio(&mut self) {
+        let result = match &self.loading_audio {
+            Some((_, loading)) => match loading.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err(eyre!("Audio file loader stopped")),
+            },
+            None => return,
+        };
+
+        if let Some((path, _)) = self.loading_audio.take() {
+            match result {
+                Ok(audio) => {
+                    self.audio = Some(audio);
+                    self.audio_file = path;
+                }
+                Err(err) => println!("{:?}", err),
+            }
+        }
+    }
+
+    fn set_palette(&mut self, palette_name: &str) -> Result<()> {
+        self.gradient = crate::svg_palette::to_gradient(palette_name)?;
+        self.palette_name = palette_name.to_string();
+        Ok(())
+    }
+}
+
+impl Program for VUMeter {
+    fn update(
+        &mut self,
+        _context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+        _frame_index: usize,
+    ) {
+        self.receive_loaded_audio();
+
+        let input_level = match &mut self.audio {
+            Some(audio) => audio.next_level(),
+            None => self.osc_level,
+        };
+
+        // The bar jumps up to louder levels and falls back smoothly
+        self.level = input_level.max(self.level * RELEASE);
+
+        if self.level >= self.peak {
+            self.peak = self.level;
+            self.ticks_until_peak_falls = self.peak_hold_ticks;
+        } else if self.ticks_until_peak_falls > 0 {
+            self.ticks_until_peak_falls -= 1;
+        } else {
+            self.peak = (self.peak - PEAK_FALL).max(self.level);
+        }
+
+        for led_strip in led_strips.iter_mut() {
+            let strip_len = led_strip.len();
+
+            // The number of LEDs from the start of the bar to the end of the strip
+            let meter_len = if self.mirrored {
+                strip_len.div_ceil(2)
+            } else {
+                strip_len
+            };
+
+            let bar_len = (self.level * meter_len as f32).round() as usize;
+            let peak_index = ((self.peak * meter_len as f32) as usize).min(meter_len - 1);
+
+            for (led_index, led_color) in led_strip.iter_mut().enumerate() {
+                // Distance of the LED along the bar
+                let meter_index = if !self.mirrored {
+                    led_index
+                } else if led_index < strip_len / 2 {
+                    strip_len / 2 - 1 - led_index
+                } else {
+                    led_index - strip_len / 2
+                };
+
+                *led_color = if meter_index < bar_len || (meter_index == peak_index && self.peak > 0.0) {
+                    self.gradient.get(meter_index as f32 / meter_len as f32 * 100.0)
+                } else {
+                    hsl(0.0, 0.0, 0.0)
+                };
+            }
+        }
+    }
+
+    fn receive_osc_packet<'a>(
+        &mut self,
+        addr:  &'a[&'a str],
+        args: &'a[nannou_osc::Type],
+        _frame_index: usize,
+    ) -> Result<()> {
+        use nannou_osc::Type::*;
+        match (addr, args) {
+            (["audio", "level"], [
+                Float(level),
+            ]) => {
+                // Levels received over OSC replace the audio file
+                self.audio = None;
+                self.audio_file.clear();
+                self.loading_audio = None;
+                self.osc_level = (level / 255.0).clamp(0.0, 1.0);
+            }
+            (["audio", "file"], [
+                String(path),
+            ]) => {
+                // The current audio keeps playing until the new file has loaded
+                let loading = WavLevels::open_in_background(PathBuf::from(path));
+                self.loading_audio = Some((path.clone(), loading));
+            }
+            (["variable", "vumeter_mirror"], [
+                Float(mirrored),
+            ]) => {
+                self.mirrored = *mirrored != 0.0;
+            }
+            (["variable", "vumeter_hold"], [
+                Float(seconds),
+            ]) => {
+                // ticks are calculated at 40 fps (but can be scaled by the program speed)
+                self.peak_hold_ticks = (seconds * 40.0)
+                    .to_usize()
+                    .ok_or_else(|| eyre!("Invalid vumeter_hold"))?;
+            }
+            (["palette", palette_name], _) => {
+                self.set_palette(palette_name)?;
+            }
+            (["palette"], [String(palette_name)]) => {
+                self.set_palette(palette_name)?;
+            }
+            _ => {
+                return Err(eyre!("Unsupported packet received. addr: {:?} args: {:?}", addr, args))
+            }
+        };
+        Ok(())
+    }
+
+    fn param_value(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            // The level is replaced by the audio file while one is playing, so restoring both
+            // (eg. from a preset) would stop the audio
+            "level" if self.audio.is_some() || self.loading_audio.is_some() => None,
+            "level" => Some(ParamValue::Float(self.osc_level * 255.0)),
+            "file" => match &self.loading_audio {
+                Some((path, _)) => Some(ParamValue::Text(path.clone())),
+                None => Some(ParamValue::Text(self.audio_file.clone())),
+            },
+            "mirror" => Some(ParamValue::Toggle(self.mirrored)),
+            // ticks are calculated at 40 fps
+            "hold" => Some(ParamValue::Float(self.peak_hold_ticks as f32 / 40.0)),
+            "palette" => Some(ParamValue::Text(self.palette_name.clone())),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use eyre::{Result, eyre};
+
+const MIN_BPM: f32 = 20.0;
+const MAX_BPM: f32 = 300.0;
+const MAX_BEATS_PER_BAR: u32 = 16;
+/// Taps further apart than this start a new tempo
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of taps averaged to calculate the tempo
+const MAX_TAPS: usize = 8;
+/// MIDI clock ticks per beat (quarter note)
+const MIDI_CLOCK_TICKS_PER_BEAT: usize = 24;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct TempoConfig {
+    pub bpm: f32,
+    pub beats_per_bar: u32,
+    /// Programs that run in time with the beat, by name, with the number of frames they run per
+    /// beat (eg. `theaterchase = 2.0` moves the theater chase one LED per eighth note)
+    pub lock: BTreeMap<String, f32>,
+}
+
+impl Default for TempoConfig {
+    fn default() -> Self {
+        Self {
+            bpm: 120.0,
+            beats_per_bar: 4,
+            lock: BTreeMap::new(),
+        }
+    }
+}
+
+impl TempoConfig {
+    pub fn validate(&self) -> Result<()> {
+        let mut clock = BeatClock::default();
+        clock.set_bpm(self.bpm)?;
+        clock.set_beats_per_bar(self.beats_per_bar as f32)?;
+
+        let mut beat_locks = BTreeMap::new();
+
+        for (program_name, ticks_per_beat) in self.lock.iter() {
+            set_beat_lock(&mut beat_locks, program_name, *ticks_per_beat)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A musical clock, counting beats in real time regardless of the program speed
+#[derive(Debug, Clone, Copy)]
+pub struct BeatClock {
+    pub bpm: f32,
+    pub beats_per_bar: u32,
+    /// Beats since startup
+    pub beats: f64,
+}
+
+impl Default for BeatClock {
+    fn default() -> Self {
+        Self {
+            bpm: 120.0,
+            beats_per_bar: 4,
+            beats: 0.0,
+        }
+    }
+}
+
+impl BeatClock {
+    pub fn new(config: &TempoConfig) -> Self {
+        Self {
+            bpm: config.bpm,
+            beats_per_bar: config.beats_per_bar,
+            beats: 0.0,
+        }
+    }
+
+    pub fn advance(&mut self, elapsed: Duration) {
+        self.beats += elapsed.as_secs_f64() * self.bpm as f64 / 60.0;
+    }
+
+    /// How far through the current beat the clock is (0 to 1)
+    pub fn beat_phase(&self) -> f32 {
+        self.beats.fract() as f32
+    }
+
+    /// How far through the current bar the clock is (0 to 1)
+    pub fn bar_phase(&self) -> f32 {
+        let beats_per_bar = self.beats_per_bar as f64;
+        ((self.beats % beats_per_bar) / beats_per_bar) as f32
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32) -> Result<()> {
+        if !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+            return Err(eyre!("bpm must be between {} and {}, got: {}", MIN_BPM, MAX_BPM, bpm));
+        }
+
+        self.bpm = bpm;
+        Ok(())
+    }
+
+    pub fn set_beats_per_bar(&mut self, beats_per_bar: f32) -> Result<()> {
+        if beats_per_bar.fract() != 0.0 || !(1.0..=MAX_BEATS_PER_BAR as f32).contains(&beats_per_bar) {
+            return Err(eyre!(
+                "beats_per_bar must be a whole number from 1 to {}, got: {}",
+                MAX_BEATS_PER_BAR,
+                beats_per_bar,
+            ));
+        }
+
+        self.beats_per_bar = beats_per_bar as u32;
+        Ok(())
+    }
+}
+
+/// Sets the tempo from the time between taps (eg. of a button on a control surface)
+#[derive(Debug, Default)]
+pub struct TapTempo {
+    /// The most recent taps
+    taps: Vec<Instant>,
+    /// Taps since the first of the current tempo
+    tap_count: usize,
+    /// The beat that the first tap landed on
+    first_beat: f64,
+}
+
+impl TapTempo {
+    /// Sets the tempo to the average time between the recent taps and lines the beats up with the
+    /// taps. The first tap of a new tempo is the start of a bar.
+    pub fn tap(&mut self, clock: &mut BeatClock) {
+        let now = Instant::now();
+
+        let is_new_tempo = !matches!(
+            self.taps.last(),
+            Some(last_tap) if now.duration_since(*last_tap) <= TAP_TIMEOUT
+        );
+
+        if is_new_tempo {
+            let beats_per_bar = clock.beats_per_bar as f64;
+
+            self.taps.clear();
+            self.tap_count = 0;
+            self.first_beat = (clock.beats / beats_per_bar).ceil() * beats_per_bar;
+        }
+
+        clock.beats = self.first_beat + self.tap_count as f64;
+
+        self.tap_count += 1;
+        self.taps.push(now);
+
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+
+        if let [first_tap, .., last_tap] = &self.taps[..] {
+            let seconds_per_beat = last_tap.duration_since(*first_tap).as_secs_f32()
+                / (self.taps.len() - 1) as f32;
+
+            clock.bpm = (60.0 / seconds_per_beat).clamp(MIN_BPM, MAX_BPM);
+        }
+    }
+}
+
+/// Follows the tempo of MIDI clock (eg. from a DAW)
+#[derive(Debug, Default)]
+pub struct MidiClock {
+    /// Times of the ticks of the last beat
+    tick_times: VecDeque<Instant>,
+    /// Ticks since the clock started
+    ticks: usize,
+    /// The beat that the clock started on
+    first_beat: f64,
+}
+
+impl MidiClock {
+    /// Starts counting beats from the start of the next bar
+    pub fn start(&mut self, clock: &mut BeatClock) {
+        let beats_per_bar = clock.beats_per_bar as f64;
+
+        self.tick_times.clear();
+        self.ticks = 0;
+        self.first_beat = (clock.beats / beats_per_bar).ceil() * beats_per_bar;
+
+        clock.beats = self.first_beat;
+    }
+
+    /// Sets the tempo to the time taken by the last beat's worth of ticks and lines the beats up
+    /// with the ticks
+    pub fn tick(&mut self, received_at: Instant, clock: &mut BeatClock) {
+        let is_stopped = !matches!(
+            self.tick_times.back(),
+            Some(last_tick) if received_at.duration_since(*last_tick) <= TAP_TIMEOUT
+        );
+
+        if is_stopped {
+            self.start(clock);
+        }
+
+        let beat = self.ticks / MIDI_CLOCK_TICKS_PER_BEAT;
+        let tick_in_beat = self.ticks % MIDI_CLOCK_TICKS_PER_BEAT;
+
+        // Line the beat up with the first tick of each beat
+        if tick_in_beat == 0 {
+            clock.beats = self.first_beat + beat as f64;
+        }
+
+        self.ticks += 1;
+        self.tick_times.push_back(received_at);
+
+        if self.tick_times.len() > MIDI_CLOCK_TICKS_PER_BEAT + 1 {
+            self.tick_times.pop_front();
+        }
+
+        if let (Some(first_tick), Some(last_tick)) = (self.tick_times.front(), self.tick_times.back()) {
+            if self.tick_times.len() == MIDI_CLOCK_TICKS_PER_BEAT + 1 {
+                let seconds_per_beat = last_tick.duration_since(*first_tick).as_secs_f32();
+                clock.bpm = (60.0 / seconds_per_beat).clamp(MIN_BPM, MAX_BPM);
+            }
+        }
+    }
+}
+
+/// Locks a program to the beat at `ticks_per_beat` frames per beat, or unlocks it if 0
+pub fn set_beat_lock(
+    beat_locks: &mut BTreeMap<String, f32>,
+    program_name: &str,
+    ticks_per_beat: f32,
+) -> Result<()> {
+    let info = crate::programs::PROGRAMS
+        .iter()
+        .find(|info| info.name == program_name)
+        .ok_or_else(|| eyre!("Invalid program name: {}", program_name))?;
+
+    if !info.beat_lockable {
+        return Err(eyre!("The {} program can't be locked to the beat", program_name));
+    }
+
+    if !ticks_per_beat.is_finite() || ticks_per_beat < 0.0 {
+        return Err(eyre!(
+            "Frames per beat for {} must be a positive number, got: {}",
+            program_name,
+            ticks_per_beat,
+        ));
+    }
+
+    if ticks_per_beat == 0.0 {
+        beat_locks.remove(program_name);
+    } else {
+        beat_locks.insert(program_name.to_string(), ticks_per_beat);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use serde::Deserialize;
+use eyre::{Result, eyre};
+
+/// The physical layout of the LED strips connected to the controller(s)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Topology {
+    pub strips: Vec<StripConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StripConfig {
+    /// Number of LEDs on the strip
+    pub len: usize,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self {
+            strips: vec![StripConfig { len: 150 }],
+        }
+    }
+}
+
+impl Topology {
+    pub fn validate(&self) -> Result<()> {
+        if self.strips.is_empty() {
+            return Err(eyre!("Topology must contain at least one LED strip"));
+        }
+
+        if let Some(strip_index) = self.strips.iter().position(|strip| strip.len == 0) {
+            return Err(eyre!("LED strip #{} must contain at least one LED", strip_index));
+        }
+
+        Ok(())
+    }
+
+    /// Creates a blank (all LEDs off) frame matching the topology
+    pub fn led_strips(&self) -> crate::LedStripVec {
+        self.strips
+            .iter()
+            .map(|strip| vec![crate::LedColor::default(); strip.len])
+            .collect()
+    }
+}
@@ -0,0 +1,151 @@
+use nannou::rand;
+use serde::Deserialize;
+use eyre::{Result, eyre};
+
+use crate::{
+    layer::Layer,
+    program::{self, ProgramContext, ProgramExecutor, crossfade},
+};
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields, default)]
+pub struct TransitionConfig {
+    #[serde(rename = "type")]
+    pub kind: TransitionKind,
+    /// Seconds (at 40 fps) to transition between programs. 0 cuts straight to the new program.
+    pub duration: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransitionKind {
+    /// Crossfades every LED at once
+    Fade,
+    /// Sweeps the new program along each strip from its first LED
+    Wipe,
+    /// Switches the LEDs over to the new program one at a time in a random order
+    Dissolve,
+}
+
+impl Default for TransitionConfig {
+    fn default() -> Self {
+        Self {
+            kind: TransitionKind::Fade,
+            duration: 1.0,
+        }
+    }
+}
+
+impl TransitionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.duration.is_finite() || self.duration < 0.0 {
+            return Err(eyre!("duration must be a positive number, got: {}", self.duration));
+        }
+
+        Ok(())
+    }
+
+    fn duration_ticks(&self) -> usize {
+        // ticks are calculated at 40 fps (but can be scaled by the program speed)
+        (self.duration * 40.0) as usize
+    }
+}
+
+impl TransitionKind {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "fade" => Ok(TransitionKind::Fade),
+            "wipe" => Ok(TransitionKind::Wipe),
+            "dissolve" => Ok(TransitionKind::Dissolve),
+            _ => Err(eyre!("Invalid transition type: {:?}", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransitionKind::Fade => "fade",
+            TransitionKind::Wipe => "wipe",
+            TransitionKind::Dissolve => "dissolve",
+        }
+    }
+}
+
+/// Keeps the outgoing program running while the incoming one is blended in over it
+#[derive(Debug)]
+pub struct Transition {
+    config: TransitionConfig,
+    outgoing: Layer,
+    /// The frame drawn by the incoming program
+    incoming_strips: crate::LedStripVec,
+    ticks: usize,
+    /// The point in the transition (0 to 1) at which each LED switches over when dissolving
+    dissolve_order: Vec<f32>,
+}
+
+impl Transition {
+    /// Starts a transition from the program that drew `led_strips`, or `None` if the transition is
+    /// a cut
+    pub fn new(
+        config: TransitionConfig,
+        outgoing: ProgramExecutor,
+        led_strips: &crate::LedStripVec,
+    ) -> Option<Self> {
+        if config.duration_ticks() == 0 {
+            return None;
+        }
+
+        Some(Self {
+            config,
+            outgoing: Layer::new(outgoing, led_strips.clone()),
+            incoming_strips: led_strips.clone(),
+            ticks: 0,
+            dissolve_order: (0..program::total_led_count(led_strips)).map(|_| rand::random()).collect(),
+        })
+    }
+
+    /// Runs one frame of both programs and blends them into `led_strips`. Returns true once the
+    /// transition has finished.
+    pub fn update(
+        &mut self,
+        incoming: &mut ProgramExecutor,
+        context: &ProgramContext,
+        led_strips: &mut crate::LedStripVec,
+    ) -> bool {
+        incoming.update(context, &mut self.incoming_strips);
+        self.outgoing.update(context);
+
+        self.ticks += 1;
+
+        if self.ticks >= self.config.duration_ticks() {
+            led_strips.clone_from(&self.incoming_strips);
+            return true;
+        }
+
+        let amount = self.ticks as f32 / self.config.duration_ticks() as f32;
+        let mut led_index = 0;
+
+        let frames = self.outgoing.led_strips.iter().zip(self.incoming_strips.iter());
+
+        for (led_strip, (from, to)) in led_strips.iter_mut().zip(frames) {
+            let strip_len = led_strip.len();
+
+            for (strip_index, (led_color, (from, to))) in led_strip
+                .iter_mut()
+                .zip(from.iter().zip(to.iter()))
+                .enumerate()
+            {
+                let switched = |position: f32| if position < amount { *to } else { *from };
+
+                *led_color = match self.config.kind {
+                    TransitionKind::Fade => crossfade(*from, *to, amount),
+                    TransitionKind::Wipe => switched((strip_index as f32 + 0.5) / strip_len as f32),
+                    TransitionKind::Dissolve => switched(self.dissolve_order[led_index]),
+                };
+
+                led_index += 1;
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,141 @@
+//! The optional nannou front end which displays the LEDs in a window
+use std::cell::RefCell;
+
+use nannou::prelude::*;
+
+use crate::Model;
+
+thread_local! {
+    // Nannou's model function can not return errors so the model is created ahead of time in
+    // `main` and handed over here.
+    static INITIAL_MODEL: RefCell<Option<Model>> = const { RefCell::new(None) };
+}
+
+/// Opens the visualizer window and runs the model until the window is closed
+pub fn run(initial_model: Model) {
+    INITIAL_MODEL.with(|cell| *cell.borrow_mut() = Some(initial_model));
+
+    nannou::app(model).update(update).run();
+}
+
+fn model(app: &App) -> Model {
+    // Configure the window
+    app.new_window()
+        .title("OSC Receiver")
+        .size(1440, 550)
+        // .raw_event(raw_window_event)
+        .view(view)
+        .build()
+        .unwrap();
+
+    INITIAL_MODEL
+        .with(|cell| cell.borrow_mut().take())
+        .expect("Model should be created before starting the app")
+}
+
+// fn raw_window_event(app: &App, model: &mut Model, event: &ui::RawWindowEvent) {
+//     model.ui.handle_raw_event(app, event);
+// }
+
+fn update(_app: &App, model: &mut Model, update: Update) {
+    crate::update(model, update.since_last);
+}
+
+const PAGE_MARGIN: f32 = 6.0;
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    // get canvas to draw on
+    let draw = app.draw();
+
+    let win_rec = app.main_window().rect();
+    let origin_x = win_rec.left() + PAGE_MARGIN;
+    let origin_y = win_rec.top() - PAGE_MARGIN;
+
+    // set background to blue
+    draw.background().color(nannou::color::BLACK);
+
+    const MAX_LED_BOX_SIZE: f32 = 8.0;
+    const LED_BORDER_SIZE: f32 = 0.5;
+    const STROKE_WEIGHT: f32 = 0.5;
+    const TEXT_HEIGHT: u32 = 14;
+
+    // Show the program's frame with any layers drawn over it
+    let led_strips = model.layers.frame().unwrap_or(&model.led_strips);
+
+    // Shrink the LEDs so that the longest strip fits within the window
+    let longest_strip_len = led_strips
+        .iter()
+        .map(|led_strip| led_strip.len())
+        .max()
+        .unwrap_or(1);
+
+    let led_box_size = ((win_rec.w() - PAGE_MARGIN * 2.0) / longest_strip_len as f32
+        - LED_BORDER_SIZE * 2.0)
+        .clamp(1.0, MAX_LED_BOX_SIZE);
+
+    for (strip_index, led_strip) in led_strips.iter().enumerate() {
+        let offset_y = origin_y - strip_index as f32 * 32.0;
+
+        let text = format!("LED STRIP #{}", strip_index);
+        draw.text(&text)
+            .color(WHITE)
+            .font_size(TEXT_HEIGHT)
+            .y(offset_y - (TEXT_HEIGHT as f32) / 2.0)
+            .wh(win_rec.wh());
+
+        for (led_index, led_color) in led_strip.iter().enumerate() {
+            let x = origin_x + (led_box_size + LED_BORDER_SIZE * 2.0) * (led_index as f32 + 0.5);
+            let y = offset_y
+                - (TEXT_HEIGHT as f32)
+                - PAGE_MARGIN
+                - (led_box_size + LED_BORDER_SIZE * 2.0) * 0.5;
+
+            draw.rect()
+                .x(x)
+                .y(y)
+                .w_h(
+                    led_box_size + LED_BORDER_SIZE * 2.0,
+                    led_box_size + LED_BORDER_SIZE * 2.0,
+                )
+                .stroke(gray(0.7))
+                .stroke_weight(STROKE_WEIGHT)
+                .color(BLACK);
+            // .hsv(1.0, 1.0, 1.0);
+
+            draw.rect()
+                .x(x - STROKE_WEIGHT / 2.0)
+                .y(y)
+                .w_h(led_box_size, led_box_size)
+                .color(*led_color);
+            // .hsv(1.0, 1.0, 1.0);
+        }
+    }
+
+    // Show the estimated power draw of each supply below the LED strips
+    if let Some(power_limiter) = &model.power_limiter {
+        let offset_y = origin_y - led_strips.len() as f32 * 32.0 - PAGE_MARGIN;
+
+        for (supply_index, supply) in power_limiter.supplies().iter().enumerate() {
+            let mut text = format!(
+                "SUPPLY #{}: {:.2}A / {:.2}A",
+                supply_index,
+                supply.estimated_amps,
+                supply.max_amps,
+            );
+
+            if supply.scale < 1.0 {
+                text += &format!(" (LIMITED TO {:.0}%)", supply.scale * 100.0);
+            }
+
+            draw.text(&text)
+                .color(if supply.scale < 1.0 { ORANGE } else { WHITE })
+                .font_size(TEXT_HEIGHT)
+                .left_justify()
+                .y(offset_y - (supply_index as f32 + 0.5) * (TEXT_HEIGHT as f32 + 4.0))
+                .wh(win_rec.wh());
+        }
+    }
+
+    // put everything on the frame
+    draw.to_frame(app, &frame).unwrap();
+}